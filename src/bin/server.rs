@@ -0,0 +1,67 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::Parser;
+
+use logicloom::function::FunctionRegistry;
+use logicloom::loader::{load_file, RuleFile};
+use logicloom::server::store::DEFAULT_RETAINED_VERSIONS;
+use logicloom::server::{router, AppState};
+
+/// Serves rule evaluation over HTTP.
+#[derive(Parser)]
+#[command(name = "logicloom-server", version)]
+struct Args {
+    /// Rule files (.json, .yaml or .yml) to serve.
+    #[arg(required = true)]
+    rules: Vec<PathBuf>,
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: SocketAddr,
+    /// How many versions of the rules to keep for rollbacks, including the current one.
+    #[arg(long, default_value_t = DEFAULT_RETAINED_VERSIONS)]
+    keep_versions: usize,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let functions = Arc::new(FunctionRegistry::default());
+    let mut rules = RuleFile::default();
+    for file in &args.rules {
+        if let Err(e) = load_file(file, &functions).and_then(|file| rules.extend(file)) {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let state = match AppState::with_functions(rules, functions) {
+        Ok(state) => {
+            state.set_retained_versions(args.keep_versions);
+            state
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let listener = match tokio::net::TcpListener::bind(args.addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("error: could not listen on {}: {}", args.addr, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("listening on {}", args.addr);
+    let shutdown = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    if let Err(e) = axum::serve(listener, router(state)).with_graceful_shutdown(shutdown).await {
+        eprintln!("error: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -1,14 +1,26 @@
 use std::collections::HashMap;
-use std::any::Any;
-use std::error::Error;
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{LogicLoomError, Result};
+use crate::function::aggregator::Aggregator;
+use crate::function::list_function::{list_function_constraints_argument_number, ListFunction, ListFunctionConstraint};
+use crate::function::pattern::{captures_groups, Captures};
+use crate::function::{FunctionCall, FunctionRegistry};
+use crate::path::{self, PathError};
+use crate::trace::{ConditionTrace, FunctionTrace};
+
+/// A condition on a single input field: every function has to hold for the value at `input_path`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Condition {
-    input_path: String,
-    functions: Vec<fn(&HashMap<String, Box<dyn Any>>) -> Result<bool, Box<dyn Error>>>,
+    pub input_path: String,
+    pub functions: Vec<FunctionCall>,
 }
 
 impl Condition {
-    pub fn new(input_path: String, functions: Vec<fn(&HashMap<String, Box<dyn Any>>)-> Result<bool, Box<dyn Error>>>) -> Self {
+    pub fn new(input_path: String, functions: Vec<FunctionCall>) -> Self {
         Condition {
             input_path,
             functions
@@ -16,27 +28,308 @@ impl Condition {
 
     }
 
-    pub fn eval(&self, input: &HashMap<String, Box<dyn Any>>) -> Result<bool, Box<dyn Error>> {
-        for function in &self.functions {
-            let result = function(input)?;
+    pub fn eval(&self, input: &Value, functions: &FunctionRegistry) -> Result<bool> {
+        self.eval_traced(input, functions, None, None)
+    }
+
+    // With `captures`, the groups of the `Matches` and `MatchesAny` calls are added to it.
+    fn eval_traced(&self, input: &Value, functions: &FunctionRegistry, captures: Option<&mut Captures>, mut trace: Option<&mut ConditionTrace>) -> Result<bool> {
+        let value = match path::resolve(input, &self.input_path) {
+            Ok(value) => value,
+            Err(missing @ PathError::NotFound { .. }) => return self.eval_missing(functions, missing, trace.map(|t| &mut t.functions)),
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.value = Some(value.clone());
+        }
+        apply_functions(value, &self.functions, functions, captures, trace.map(|t| &mut t.functions))
+            .map_err(|e| e.at_path(&self.input_path))
+    }
+
+    // Nothing is at the input path: the functions are not called, their `on_missing` results are used instead.
+    fn eval_missing(&self, functions: &FunctionRegistry, missing: PathError, mut trace: Option<&mut Vec<FunctionTrace>>) -> Result<bool> {
+        for call in &self.functions {
+            if !functions.single.contains_key(&call.function) {
+                return Err(LogicLoomError::unknown_function(call.function));
+            }
+            let Some(result) = functions.signatures.single.get(&call.function).and_then(|signature| signature.on_missing) else {
+                return Err(missing.into());
+            };
+            if let Some(trace) = trace.as_deref_mut() {
+                trace.push(FunctionTrace::new(call.function, call.args.clone(), &Ok(result)));
+            }
             if !result {
                 return Ok(false);
             }
         }
         Ok(true)
     }
+}
 
+// True when every function holds for the value. With `captures`, `Matches` and `MatchesAny` are evaluated by the
+// registry's patterns so that the groups of the pattern that matched are captured by the same search.
+fn apply_functions(
+    value: &Value,
+    calls: &[FunctionCall],
+    functions: &FunctionRegistry,
+    mut captures: Option<&mut Captures>,
+    mut trace: Option<&mut Vec<FunctionTrace>>,
+) -> Result<bool> {
+    for call in calls {
+        let function = functions.single.get(&call.function)
+            .ok_or_else(|| LogicLoomError::unknown_function(call.function))?;
+        let result = match captures.as_deref_mut() {
+            Some(captures) if captures_groups(call.function) => functions.patterns.captures(call.function, value, &call.args)
+                .map(|found| found.map(|found| captures.extend(found)).is_some()),
+            _ => function(value, &call.args),
+        };
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.push(FunctionTrace::new(call.function, call.args.clone(), &result));
+        }
+        if !result? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
 
+/// A condition over a list: the functions are applied to every element and the number
+/// of elements that pass is checked with `constraint`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListCondition {
+    pub input_path: String,
+    pub functions: Vec<FunctionCall>,
+    pub constraint: ListConstraint,
 }
 
-enum LogicalOperator {
+impl ListCondition {
+    pub fn eval(&self, input: &Value, functions: &FunctionRegistry) -> Result<bool> {
+        self.eval_traced(input, functions, None)
+    }
+
+    fn eval_traced(&self, input: &Value, functions: &FunctionRegistry, mut trace: Option<&mut ConditionTrace>) -> Result<bool> {
+        let elements = path::resolve_list(input, &self.input_path)?;
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.value = Some(Value::Array(elements.iter().map(|element| (*element).clone()).collect()));
+        }
+        let mut passed = 0;
+        for element in &elements {
+            let mut element_trace = trace.is_some().then(|| ConditionTrace::new("element", None));
+            let result = apply_functions(element, &self.functions, functions, None, element_trace.as_mut().map(|t| &mut t.functions))
+                .map_err(|e| e.at_path(&self.input_path));
+            if let (Some(trace), Some(mut element_trace)) = (trace.as_deref_mut(), element_trace) {
+                element_trace.value = Some((*element).clone());
+                element_trace.finish(&result);
+                trace.conditions.push(element_trace);
+            }
+            if result? {
+                passed += 1;
+            }
+        }
+        let result = self.constraint.check(elements.len(), passed, &functions.list).map_err(|e| e.at_path(&self.input_path));
+        if let Some(trace) = trace {
+            let args = self.constraint.args.iter().map(|&arg| Value::from(arg)).collect();
+            trace.functions.push(FunctionTrace::new(self.constraint.function, args, &result));
+        }
+        result
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListConstraint {
+    pub function: ListFunction,
+    #[serde(default)]
+    pub args: Vec<i32>,
+}
+
+impl ListConstraint {
+    pub fn check(&self, list_total: usize, passed_total: usize, functions: &HashMap<ListFunction, ListFunctionConstraint>) -> Result<bool> {
+        let expected = list_function_constraints_argument_number(self.function) as usize;
+        if self.args.len() != expected {
+            return Err(LogicLoomError::argument_count(self.function, expected, self.args.len()));
+        }
+        if let Some(arg) = self.args.iter().find(|arg| **arg < 0) {
+            return Err(LogicLoomError::function(self.function, format!("takes no negative arguments but got {}", arg)));
+        }
+        if matches!(self.function, ListFunction::AtLeastFraction | ListFunction::AtMostFraction) && self.args[1] == 0 {
+            return Err(LogicLoomError::function(self.function, "the denominator is 0"));
+        }
+        let constraint = functions.get(&self.function)
+            .ok_or_else(|| LogicLoomError::unknown_function(self.function))?;
+        Ok(constraint(list_total, passed_total, &self.args))
+    }
+}
+
+/// A condition over a list reduced to a single number by `aggregator`, which the functions are then applied to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AggregateCondition {
+    pub input_path: String,
+    pub aggregator: Aggregator,
+    pub functions: Vec<FunctionCall>,
+}
+
+impl AggregateCondition {
+    pub fn eval(&self, input: &Value, functions: &FunctionRegistry) -> Result<bool> {
+        self.eval_traced(input, functions, None)
+    }
+
+    fn eval_traced(&self, input: &Value, functions: &FunctionRegistry, mut trace: Option<&mut ConditionTrace>) -> Result<bool> {
+        let elements = path::resolve_list(input, &self.input_path)?;
+        let aggregate = functions.aggregate.get(&self.aggregator)
+            .ok_or_else(|| LogicLoomError::unknown_function(self.aggregator).at_path(&self.input_path))?;
+        let result = aggregate(&elements).map_err(|e| e.at_path(&self.input_path))?;
+        let value = serde_json::Number::from_f64(result)
+            .map(Value::Number)
+            .ok_or_else(|| LogicLoomError::parse(self.aggregator, format!("result [{}] is not a number", result)).at_path(&self.input_path))?;
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.value = Some(value.clone());
+        }
+        apply_functions(&value, &self.functions, functions, None, trace.map(|t| &mut t.functions))
+            .map_err(|e| e.at_path(&self.input_path))
+    }
+}
+
+/// One entry of a rule's `conditions`, keyed by its kind in the rule file. `and`, `or`, `xor`
+/// and `not` combine other conditions, so conditions can be nested to any depth.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionExpr {
+    Single(Condition),
+    List(ListCondition),
+    Aggregate(AggregateCondition),
+    And(Vec<ConditionExpr>),
+    Or(Vec<ConditionExpr>),
+    Xor(Vec<ConditionExpr>),
+    Not(Box<ConditionExpr>),
+}
+
+impl ConditionExpr {
+    pub fn single(input_path: &str, functions: Vec<FunctionCall>) -> Self {
+        ConditionExpr::Single(Condition::new(input_path.to_owned(), functions))
+    }
+
+    pub fn eval(&self, input: &Value, context: &mut EvalContext) -> Result<bool> {
+        let Some(siblings) = context.trace.take() else {
+            return self.eval_traced(input, context, None);
+        };
+
+        // The conditions this one evaluates are collected in a fresh list and become its children.
+        context.trace = Some(Vec::new());
+        let mut trace = ConditionTrace::new(self.kind(), self.input_path());
+        let result = self.eval_traced(input, context, Some(&mut trace));
+        let children = context.trace.replace(siblings).unwrap_or_default();
+        trace.conditions.extend(children);
+        trace.finish(&result);
+        if let Some(siblings) = context.trace.as_mut() {
+            siblings.push(trace);
+        }
+        result
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ConditionExpr::Single(_) => "single",
+            ConditionExpr::List(_) => "list",
+            ConditionExpr::Aggregate(_) => "aggregate",
+            ConditionExpr::And(_) => "and",
+            ConditionExpr::Or(_) => "or",
+            ConditionExpr::Xor(_) => "xor",
+            ConditionExpr::Not(_) => "not",
+        }
+    }
+
+    fn input_path(&self) -> Option<&str> {
+        match self {
+            ConditionExpr::Single(condition) => Some(&condition.input_path),
+            ConditionExpr::List(condition) => Some(&condition.input_path),
+            ConditionExpr::Aggregate(condition) => Some(&condition.input_path),
+            _ => None,
+        }
+    }
+
+    // The groups captured inside a condition are collected apart and only kept when the condition holds, so a
+    // branch that failed or a negated match does not leak its groups into the output.
+    fn eval_traced(&self, input: &Value, context: &mut EvalContext, trace: Option<&mut ConditionTrace>) -> Result<bool> {
+        let outer = std::mem::take(&mut context.captures);
+        let result = self.eval_scoped(input, context, trace);
+        let captured = std::mem::replace(&mut context.captures, outer);
+        if matches!(result, Ok(true)) && !matches!(self, ConditionExpr::Not(_)) {
+            context.captures.extend(captured);
+        }
+        result
+    }
+
+    fn eval_scoped(&self, input: &Value, context: &mut EvalContext, trace: Option<&mut ConditionTrace>) -> Result<bool> {
+        let functions = context.functions;
+        match self {
+            ConditionExpr::Single(condition) => condition.eval_traced(input, functions, Some(&mut context.captures), trace),
+            ConditionExpr::List(condition) => condition.eval_traced(input, functions, trace),
+            ConditionExpr::Aggregate(condition) => condition.eval_traced(input, functions, trace),
+            ConditionExpr::And(conditions) => ConditionType::new(LogicalOperator::AND).evaluate(input, conditions, context),
+            ConditionExpr::Or(conditions) => ConditionType::new(LogicalOperator::OR).evaluate(input, conditions, context),
+            ConditionExpr::Xor(conditions) => ConditionType::new(LogicalOperator::XOR).evaluate(input, conditions, context),
+            ConditionExpr::Not(condition) => Ok(!condition.eval(input, context)?),
+        }
+    }
+}
+
+impl std::ops::Not for ConditionExpr {
+    type Output = ConditionExpr;
+
+    fn not(self) -> ConditionExpr {
+        ConditionExpr::Not(Box::new(self))
+    }
+}
+
+/// How the results of a list of conditions are combined. `XOR` holds when exactly one condition holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LogicalOperator {
     OR,
+    #[default]
     AND,
+    XOR,
+}
+
+/// What a chain does when one of its conditions fails with an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ErrorPolicy {
+    /// Stop and return the error.
+    #[default]
+    Propagate,
+    /// Count the condition as false.
+    TreatAsFalse,
+    /// Count the condition as false and keep the error in `EvalContext::errors`.
+    Collect,
+}
+
+/// State shared by the conditions of one rule while it is evaluated.
+pub struct EvalContext<'a> {
+    pub functions: &'a FunctionRegistry,
+    pub on_error: ErrorPolicy,
+    pub errors: Vec<LogicLoomError>,
+    /// When set, every evaluated condition appends how it was evaluated.
+    pub trace: Option<Vec<ConditionTrace>>,
+    /// The groups captured by the regular expressions of the conditions that held, for the output templates.
+    pub captures: Captures,
+}
+
+impl<'a> EvalContext<'a> {
+    pub fn new(functions: &'a FunctionRegistry, on_error: ErrorPolicy) -> Self {
+        EvalContext { functions, on_error, errors: Vec::new(), trace: None, captures: Captures::new() }
+    }
+
+    pub fn traced(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
 }
 
 pub struct ConditionType {
     chain_type: LogicalOperator
-} 
+}
 
 impl ConditionType {
 
@@ -44,159 +337,404 @@ impl ConditionType {
         ConditionType { chain_type }
     }
 
-    pub fn evaluate(&self, input: &HashMap<String, Box<dyn Any>>, condition: Vec<Condition>) -> Result<bool, Box<dyn Error>> {
+    pub fn evaluate(&self, input: &Value, condition: &[ConditionExpr], context: &mut EvalContext) -> Result<bool> {
       match self.chain_type {
          LogicalOperator::OR => {
-            self.evaluate_or(input, condition)
+            self.evaluate_or(input, condition, context)
          }
          LogicalOperator::AND => {
-           self.evaluate_and(input, condition)
+           self.evaluate_and(input, condition, context)
          }
-         
+         LogicalOperator::XOR => {
+           self.evaluate_xor(input, condition, context)
+         }
+
 
       }
     }
 
-    pub fn evaluate_or(&self,  input: &HashMap<String, Box<dyn Any>>, conditions: Vec<Condition>) -> Result<bool, Box<dyn Error>> {
+    pub fn evaluate_or(&self,  input: &Value, conditions: &[ConditionExpr], context: &mut EvalContext) -> Result<bool> {
         for condition in conditions {
-            println!("Evaluating condition with input_path: {}", condition.input_path);
-            if condition.eval(input)? {
+            if self.eval_condition(input, condition, context)? {
                 return Ok(true);
             }
         }
         Ok(false)
-       
+
     }
 
-    pub fn evaluate_and(&self,  input: &HashMap<String, Box<dyn Any>>, conditions: Vec<Condition>) -> Result<bool, Box<dyn Error>> {
-        let mut count = conditions.len();
+    pub fn evaluate_and(&self,  input: &Value, conditions: &[ConditionExpr], context: &mut EvalContext) -> Result<bool> {
         for condition in conditions {
-            if condition.eval(input)? {
-                 count -= 1;
+            if !self.eval_condition(input, condition, context)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn evaluate_xor(&self,  input: &Value, conditions: &[ConditionExpr], context: &mut EvalContext) -> Result<bool> {
+        let mut passed = 0;
+        for condition in conditions {
+            if self.eval_condition(input, condition, context)? {
+                passed += 1;
+                // A second condition that holds decides the chain.
+                if passed > 1 {
+                    return Ok(false);
+                }
             }
         }
 
-        Ok(count == 0)
+        Ok(passed == 1)
     }
-    
+
+    fn eval_condition(&self, input: &Value, condition: &ConditionExpr, context: &mut EvalContext) -> Result<bool> {
+        match condition.eval(input, context) {
+            Ok(result) => Ok(result),
+            Err(e) => match context.on_error {
+                ErrorPolicy::Propagate => Err(e),
+                ErrorPolicy::TreatAsFalse => Ok(false),
+                ErrorPolicy::Collect => {
+                    context.errors.push(e);
+                    Ok(false)
+                }
+            },
+        }
+    }
+
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::error::Error;
-    use std::any::Any;
+    use crate::function::SingleInputFunction;
+    use serde_json::json;
 
-    // A helper function that always returns Ok(true)
-    fn always_true(_: &HashMap<String, Box<dyn Any>>) -> Result<bool, Box<dyn Error>> {
-        Ok(true)
-    }
-
-    // A helper function that always returns Ok(false)
-    fn always_false(_: &HashMap<String, Box<dyn Any>>) -> Result<bool, Box<dyn Error>> {
-        Ok(false)
-    }
-
-    fn sum_values(input: &HashMap<String, Box<dyn Any>>) -> Result<bool, Box<dyn Error>> {
-        let sum = input
-            .values()
-            .try_fold(0, |acc, value| {
-                if let Some(number) = value.downcast_ref::<i32>() {
-                    Ok(acc + number)
-                } else {
-                    Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "value is not an i32")))
-                }
-            })?;
-    
-        Ok(sum == 4) // We expect the sum to be 4 for the test case
+    fn call(function: SingleInputFunction, args: Vec<Value>) -> FunctionCall {
+        FunctionCall::new(function, args)
     }
 
     // A test case for when all functions return true
     #[test]
     fn evaluate_all_true() {
         let condition = Condition::new(
-            "input_path".to_string(),
-            vec![always_true as fn(&HashMap<String, Box<dyn Any>>) -> Result<bool, Box<dyn Error>>],
+            "name".to_string(),
+            vec![call(SingleInputFunction::NonEmpty, vec![]), call(SingleInputFunction::StartsWith, vec![json!("An")])],
         );
-        let input = HashMap::new();
-        assert_eq!(condition.eval(&input).unwrap(), true);
+        let input = json!({"name": "Ankit"});
+        assert!(condition.eval(&input, &FunctionRegistry::default()).unwrap());
     }
 
     // A test case for when one function returns false
     #[test]
     fn evaluate_one_false() {
         let condition = Condition::new(
-            "input_path".to_string(),
-            vec![always_true, always_false],
+            "name".to_string(),
+            vec![call(SingleInputFunction::NonEmpty, vec![]), call(SingleInputFunction::Empty, vec![])],
         );
-        let input = HashMap::new();
-        assert_eq!(condition.eval(&input).unwrap(), false);
+        let input = json!({"name": "Ankit"});
+        assert!(!condition.eval(&input, &FunctionRegistry::default()).unwrap());
     }
 
     // A test case for when a function returns an error
     #[test]
     fn evaluate_function_error() {
-        fn error_function(_: &HashMap<String, Box<dyn Any>>) -> Result<bool, Box<dyn Error>> {
-            Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "error")))
-        }
-
         let condition = Condition::new(
-            "input_path".to_string(),
-            vec![always_true, error_function],
+            "name".to_string(),
+            vec![call(SingleInputFunction::NonEmpty, vec![]), call(SingleInputFunction::Greater, vec![json!(1)])],
         );
-        let input = HashMap::new();
-        assert!(condition.eval(&input).is_err());
+        let input = json!({"name": "Ankit"});
+        assert!(condition.eval(&input, &FunctionRegistry::default()).is_err());
     }
 
     #[test]
-    fn evaluate_sum_of_values() {
-        let mut input = HashMap::new();
-        input.insert("key1".to_string(), Box::new(2) as Box<dyn Any> );
-        input.insert("key2".to_string(), Box::new(2) as Box<dyn Any> );
+    fn evaluate_only_input_path_value() {
+        let input = json!({"key1": 2, "key2": ""});
 
         let condition = Condition::new(
-            "input_path".to_string(),
-            vec![sum_values],
+            "key1".to_string(),
+            vec![call(SingleInputFunction::Equal, vec![json!(2)])],
         );
 
-        assert_eq!(condition.eval(&input).unwrap(), true);
+        assert!(condition.eval(&input, &FunctionRegistry::default()).unwrap());
     }
 
-    fn age_less_than_30(input: &HashMap<String, Box<dyn Any>>) -> Result<bool, Box<dyn Error>> {
-        if let Some(age) = input.get("age").and_then(|v| v.downcast_ref::<i32>()) {
-            Ok(*age < 30)
-        } else {
-            Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "age is not an i32")))
-        }
+    #[test]
+    fn evaluate_nested_input_path() {
+        let input = json!({"customer": {"address": {"country": "IN"}}, "items": [{"sku": "A-1"}]});
+
+        let condition = Condition::new("customer.address.country".to_string(), vec![call(SingleInputFunction::Equal, vec![json!("IN")])]);
+        assert!(condition.eval(&input, &FunctionRegistry::default()).unwrap());
+
+        let condition = Condition::new("items[0].sku".to_string(), vec![call(SingleInputFunction::StartsWith, vec![json!("A-")])]);
+        assert!(condition.eval(&input, &FunctionRegistry::default()).unwrap());
+
+        let condition = Condition::new("customer.phone".to_string(), vec![call(SingleInputFunction::NonEmpty, vec![])]);
+        let err = condition.eval(&input, &FunctionRegistry::default()).unwrap_err();
+        assert_eq!(err.to_string(), "path [customer.phone]: path not found");
+
+        let condition = Condition::new("customer.address.country".to_string(), vec![call(SingleInputFunction::Greater, vec![json!(1)])]);
+        let err = condition.eval(&input, &FunctionRegistry::default()).unwrap_err();
+        assert_eq!(err.to_string(), "path [customer.address.country]: [Greater]: could not convert input [\"IN\"] to number");
     }
 
-    fn name_length_greater_than_10(input: &HashMap<String, Box<dyn Any>>) -> Result<bool, Box<dyn Error>> {
-        if let Some(name) = input.get("name").and_then(|v| v.downcast_ref::<String>()) {
-            Ok(name.len() > 10)
-        } else {
-            Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "name is not a String")))
-        }
+    #[test]
+    fn evaluate_missing_values() {
+        let input = json!({"customer": {"email": null, "tags": [], "age": "42"}});
+        let functions = FunctionRegistry::default();
+        let eval = |path: &str, calls: Vec<FunctionCall>| Condition::new(path.to_owned(), calls).eval(&input, &functions);
+
+        assert!(eval("customer.email", vec![call(SingleInputFunction::Exists, vec![])]).unwrap());
+        assert!(eval("customer.email", vec![call(SingleInputFunction::IsNull, vec![])]).unwrap());
+        assert!(!eval("customer.phone", vec![call(SingleInputFunction::Exists, vec![])]).unwrap());
+        assert!(!eval("customer.phone", vec![call(SingleInputFunction::IsNull, vec![])]).unwrap());
+        assert!(!eval("order.total", vec![call(SingleInputFunction::IsNumber, vec![])]).unwrap());
+        assert!(!eval("customer.age", vec![call(SingleInputFunction::IsNumber, vec![])]).unwrap());
+        assert!(eval("customer.tags", vec![call(SingleInputFunction::IsList, vec![]), call(SingleInputFunction::Empty, vec![])]).unwrap());
+
+        // "If present then": the functions after a predicate are only called on a value that is there.
+        let present_and_short = || vec![call(SingleInputFunction::Exists, vec![]), call(SingleInputFunction::Lower, vec![json!(10)])];
+        assert!(!eval("customer.phone", present_and_short()).unwrap());
+        assert!(!eval("customer.age", present_and_short()).unwrap());
+
+        let err = eval("customer.phone", vec![call(SingleInputFunction::NonEmpty, vec![]), call(SingleInputFunction::Exists, vec![])]).unwrap_err();
+        assert_eq!(err.to_string(), "path [customer.phone]: path not found");
+        let err = eval("customer.email", vec![call(SingleInputFunction::NonEmpty, vec![])]).unwrap_err();
+        assert_eq!(err.to_string(), "path [customer.email]: [NonEmpty]: input is not a string, list or object, found null");
+    }
+
+    #[test]
+    fn trace_missing_values() {
+        let functions = FunctionRegistry::default();
+        let condition = ConditionExpr::single("coupon", vec![call(SingleInputFunction::Exists, vec![]), call(SingleInputFunction::StartsWith, vec![json!("X")])]);
+        let mut context = EvalContext::new(&functions, ErrorPolicy::Propagate).traced();
+        assert!(!condition.eval(&json!({}), &mut context).unwrap());
+
+        let trace = &context.trace.unwrap()[0];
+        assert_eq!(trace.value, None);
+        assert_eq!(trace.functions.len(), 1);
+        assert_eq!(trace.functions[0].function, "Exists");
+        assert_eq!(trace.functions[0].result, Some(false));
+    }
+
+    #[test]
+    fn evaluate_list_condition() {
+        let input = json!({"order": {"items": [{"price": 120}, {"price": 80}, {"price": 150}]}});
+        let functions = FunctionRegistry::default();
+        let over_100 = vec![call(SingleInputFunction::Greater, vec![json!(100)])];
+        let condition = |function, args| ListCondition {
+            input_path: "order.items.price".to_string(),
+            functions: over_100.clone(),
+            constraint: ListConstraint { function, args },
+        };
+
+        assert!(condition(ListFunction::AtLeast, vec![2]).eval(&input, &functions).unwrap());
+        assert!(!condition(ListFunction::AtLeast, vec![3]).eval(&input, &functions).unwrap());
+        assert!(condition(ListFunction::AtMost, vec![2]).eval(&input, &functions).unwrap());
+        assert!(condition(ListFunction::Exactly, vec![2]).eval(&input, &functions).unwrap());
+        assert!(!condition(ListFunction::All, vec![]).eval(&input, &functions).unwrap());
+        assert!(!condition(ListFunction::NoneElement, vec![]).eval(&input, &functions).unwrap());
+        assert!(condition(ListFunction::AtLeastFraction, vec![2, 3]).eval(&input, &functions).unwrap());
+        assert!(!condition(ListFunction::AtMostFraction, vec![1, 2]).eval(&input, &functions).unwrap());
+        assert!(condition(ListFunction::AtLeast, vec![]).eval(&input, &functions).is_err());
+
+        let err = condition(ListFunction::AtMost, vec![-1]).eval(&input, &functions).unwrap_err();
+        assert_eq!(err.code(), "function");
+        assert_eq!(err.to_string(), "path [order.items.price]: [AtMost]: takes no negative arguments but got -1");
+        let err = condition(ListFunction::AtLeastFraction, vec![1, 0]).eval(&input, &functions).unwrap_err();
+        assert_eq!(err.to_string(), "path [order.items.price]: [AtLeastFraction]: the denominator is 0");
+    }
+
+    #[test]
+    fn trace_list_condition() {
+        let functions = FunctionRegistry::default();
+        let condition = ConditionExpr::List(ListCondition {
+            input_path: "order.items.price".to_string(),
+            functions: vec![call(SingleInputFunction::Greater, vec![json!(100)])],
+            constraint: ListConstraint { function: ListFunction::AtLeast, args: vec![1] },
+        });
+        let mut context = EvalContext::new(&functions, ErrorPolicy::Propagate).traced();
+        assert!(condition.eval(&json!({"order": {"items": [{"price": 120}, {"price": 80}]}}), &mut context).unwrap());
+
+        let trace = &context.trace.unwrap()[0];
+        assert_eq!(trace.value, Some(json!([120, 80])));
+        assert_eq!(trace.conditions.iter().map(|element| element.value.clone()).collect::<Vec<_>>(), [Some(json!(120)), Some(json!(80))]);
+    }
+
+    #[test]
+    fn evaluate_aggregate_condition() {
+        let input = json!({"cart": {"items": [{"price": 250}, {"price": "199.99"}, {"price": 60}]}});
+        let functions = FunctionRegistry::default();
+        let condition = |aggregator, function, args| AggregateCondition {
+            input_path: "cart.items.price".to_string(),
+            aggregator,
+            functions: vec![call(function, args)],
+        };
+
+        assert!(condition(Aggregator::SUM, SingleInputFunction::GreaterEq, vec![json!(500)]).eval(&input, &functions).unwrap());
+        assert!(!condition(Aggregator::SUM, SingleInputFunction::GreaterEq, vec![json!(510)]).eval(&input, &functions).unwrap());
+        assert!(condition(Aggregator::MIN, SingleInputFunction::Equal, vec![json!(60)]).eval(&input, &functions).unwrap());
+        assert!(condition(Aggregator::MAX, SingleInputFunction::BetweenEq, vec![json!(200), json!(250)]).eval(&input, &functions).unwrap());
+        assert!(condition(Aggregator::COUNT, SingleInputFunction::Equal, vec![json!(3)]).eval(&input, &functions).unwrap());
+        assert!(condition(Aggregator::AVG, SingleInputFunction::Lower, vec![json!(170)]).eval(&input, &functions).unwrap());
     }
 
     #[test]
     fn evaluate_or_condition() {
-        let condition_age = Condition::new("age".to_string(), vec![age_less_than_30]);
-        let condition_name = Condition::new("name".to_string(), vec![name_length_greater_than_10]);
+        let condition_age = ConditionExpr::Single(Condition::new("age".to_string(), vec![call(SingleInputFunction::Lower, vec![json!(30)])]));
+        let condition_name = ConditionExpr::Single(Condition::new("name".to_string(), vec![call(SingleInputFunction::StartsWith, vec![json!("Sheoran")])]));
 
-        let mut input = HashMap::new();
-        input.insert("name".to_string(), Box::new("Ankit".to_string()) as Box<dyn Any>);
-        input.insert("age".to_string(), Box::new(28) as Box<dyn Any>);
+        let input = json!({"name": "Ankit", "age": 28});
 
+        let functions = FunctionRegistry::default();
         let condition_type = ConditionType::new(LogicalOperator::OR);
-        let result = condition_type.evaluate(&input, vec![condition_age, condition_name]);
+        let result = condition_type.evaluate(&input, &[condition_age, condition_name], &mut EvalContext::new(&functions, ErrorPolicy::Propagate));
 
-        assert_eq!(result.unwrap(), true);
+        assert!(result.unwrap());
     }
-}
 
+    #[test]
+    fn evaluate_nested_conditions() {
+        let functions = FunctionRegistry::default();
+        // AND(country == IN, OR(age < 30, NOT(tier == gold)))
+        let condition = ConditionExpr::And(vec![
+            ConditionExpr::single("country", vec![call(SingleInputFunction::Equal, vec![json!("IN")])]),
+            ConditionExpr::Or(vec![
+                ConditionExpr::single("age", vec![call(SingleInputFunction::Lower, vec![json!(30)])]),
+                !ConditionExpr::single("tier", vec![call(SingleInputFunction::Equal, vec![json!("gold")])]),
+            ]),
+        ]);
+
+        assert!(condition.eval(&json!({"country": "IN", "age": 45, "tier": "silver"}), &mut EvalContext::new(&functions, ErrorPolicy::Propagate)).unwrap());
+        assert!(condition.eval(&json!({"country": "IN", "age": 25, "tier": "gold"}), &mut EvalContext::new(&functions, ErrorPolicy::Propagate)).unwrap());
+        assert!(!condition.eval(&json!({"country": "IN", "age": 45, "tier": "gold"}), &mut EvalContext::new(&functions, ErrorPolicy::Propagate)).unwrap());
+        assert!(!condition.eval(&json!({"country": "US", "age": 25, "tier": "silver"}), &mut EvalContext::new(&functions, ErrorPolicy::Propagate)).unwrap());
+    }
 
+    #[test]
+    fn evaluate_xor_condition() {
+        let functions = FunctionRegistry::default();
+        let condition = ConditionExpr::Xor(vec![
+            ConditionExpr::single("a", vec![call(SingleInputFunction::Equal, vec![json!(true)])]),
+            ConditionExpr::single("b", vec![call(SingleInputFunction::Equal, vec![json!(true)])]),
+            ConditionExpr::single("c", vec![call(SingleInputFunction::Equal, vec![json!(true)])]),
+        ]);
+
+        assert!(condition.eval(&json!({"a": false, "b": true, "c": false}), &mut EvalContext::new(&functions, ErrorPolicy::Propagate)).unwrap());
+        assert!(!condition.eval(&json!({"a": true, "b": true, "c": false}), &mut EvalContext::new(&functions, ErrorPolicy::Propagate)).unwrap());
+        assert!(!condition.eval(&json!({"a": false, "b": false, "c": false}), &mut EvalContext::new(&functions, ErrorPolicy::Propagate)).unwrap());
+    }
 
+    #[test]
+    fn evaluate_chains_short_circuit() {
+        let functions = FunctionRegistry::default();
+        let passes = ConditionExpr::single("age", vec![call(SingleInputFunction::Greater, vec![json!(18)])]);
+        let fails = ConditionExpr::single("age", vec![call(SingleInputFunction::Lower, vec![json!(18)])]);
+        // `missing` does not exist in the input, so evaluating this condition is an error.
+        let errors = ConditionExpr::single("missing", vec![call(SingleInputFunction::NonEmpty, vec![])]);
+        let input = json!({"age": 30});
+        let mut context = EvalContext::new(&functions, ErrorPolicy::Propagate);
+
+        let and = ConditionType::new(LogicalOperator::AND);
+        assert!(!and.evaluate(&input, &[fails.clone(), errors.clone()], &mut context).unwrap());
+        assert!(and.evaluate(&input, &[passes.clone(), errors.clone()], &mut context).is_err());
+
+        let or = ConditionType::new(LogicalOperator::OR);
+        assert!(or.evaluate(&input, &[passes.clone(), errors.clone()], &mut context).unwrap());
+        assert!(or.evaluate(&input, &[fails.clone(), errors.clone()], &mut context).is_err());
+
+        let xor = ConditionType::new(LogicalOperator::XOR);
+        assert!(!xor.evaluate(&input, &[passes.clone(), passes.clone(), errors.clone()], &mut context).unwrap());
+    }
 
+    #[test]
+    fn evaluate_chain_error_policies() {
+        let functions = FunctionRegistry::default();
+        let passes = ConditionExpr::single("age", vec![call(SingleInputFunction::Greater, vec![json!(18)])]);
+        let errors = ConditionExpr::single("missing", vec![call(SingleInputFunction::NonEmpty, vec![])]);
+        let input = json!({"age": 30});
+        let or = ConditionType::new(LogicalOperator::OR);
+        let conditions = [errors.clone(), passes.clone()];
+
+        let mut context = EvalContext::new(&functions, ErrorPolicy::Propagate);
+        assert!(or.evaluate(&input, &conditions, &mut context).is_err());
+
+        let mut context = EvalContext::new(&functions, ErrorPolicy::TreatAsFalse);
+        assert!(or.evaluate(&input, &conditions, &mut context).unwrap());
+        assert!(context.errors.is_empty());
+
+        let mut context = EvalContext::new(&functions, ErrorPolicy::Collect);
+        let nested = [ConditionExpr::And(vec![passes.clone(), errors.clone()]), !errors.clone()];
+        assert!(!or.evaluate(&input, &nested, &mut context).unwrap());
+        assert_eq!(context.errors.len(), 2);
+        assert!(matches!(&context.errors[0], LogicLoomError::MissingPath { missing, .. } if missing == "missing"));
+    }
 
+    #[test]
+    fn evaluate_with_trace() {
+        let functions = FunctionRegistry::default();
+        let conditions = [
+            ConditionExpr::Or(vec![
+                ConditionExpr::single("age", vec![call(SingleInputFunction::Lower, vec![json!(30)])]),
+                ConditionExpr::single("tier", vec![call(SingleInputFunction::NonEmpty, vec![]), call(SingleInputFunction::Equal, vec![json!("gold")])]),
+                ConditionExpr::single("never", vec![]),
+            ]),
+            ConditionExpr::List(ListCondition {
+                input_path: "items.price".to_string(),
+                functions: vec![call(SingleInputFunction::Greater, vec![json!(100)])],
+                constraint: ListConstraint { function: ListFunction::AtLeast, args: vec![1] },
+            }),
+        ];
+        let input = json!({"age": 45, "tier": "gold", "items": [{"price": 50}, {"price": 150}]});
+        let mut context = EvalContext::new(&functions, ErrorPolicy::Propagate).traced();
+
+        assert!(ConditionType::new(LogicalOperator::AND).evaluate(&input, &conditions, &mut context).unwrap());
+
+        let trace = context.trace.unwrap();
+        assert_eq!(trace.len(), 2);
+        let or = &trace[0];
+        assert_eq!((or.kind, or.result), ("or", Some(true)));
+        // `never` is not evaluated, the second condition already decides the chain.
+        assert_eq!(or.conditions.len(), 2);
+        assert_eq!(or.conditions[0].value, Some(json!(45)));
+        assert_eq!(or.conditions[0].functions[0].result, Some(false));
+        assert_eq!(or.conditions[1].functions.len(), 2);
+        assert_eq!(or.conditions[1].functions[1].args, vec![json!("gold")]);
+
+        let list = &trace[1];
+        assert_eq!(list.input_path.as_deref(), Some("items.price"));
+        assert_eq!(list.conditions.iter().map(|e| e.result).collect::<Vec<_>>(), vec![Some(false), Some(true)]);
+        assert_eq!(list.functions[0].function, "AtLeast");
+        assert_eq!(list.functions[0].result, Some(true));
+    }
+
+    #[test]
+    fn deserialize_nested_conditions() {
+        let condition: ConditionExpr = serde_yaml::from_str(r#"
+and:
+  - single: {input_path: country, functions: [{function: Equal, args: [IN]}]}
+  - or:
+      - single: {input_path: age, functions: [{function: Lower, args: [30]}]}
+      - not:
+          single: {input_path: tier, functions: [{function: Equal, args: [gold]}]}
+"#).unwrap();
+
+        assert!(matches!(&condition, ConditionExpr::And(c) if matches!(&c[1], ConditionExpr::Or(o) if matches!(&o[1], ConditionExpr::Not(_)))));
+        let functions = FunctionRegistry::default();
+        assert!(condition.eval(&json!({"country": "IN", "age": 45, "tier": "silver"}), &mut EvalContext::new(&functions, ErrorPolicy::Propagate)).unwrap());
+    }
 
+    #[test]
+    fn deserialize_condition_kinds() {
+        let conditions: Vec<ConditionExpr> = serde_json::from_str(r#"[
+            {"single": {"input_path": "age", "functions": [{"function": "Greater", "args": [18]}]}},
+            {"list": {"input_path": "items", "functions": [{"function": "Greater", "args": [100]}], "constraint": {"function": "AtLeast", "args": [2]}}},
+            {"aggregate": {"input_path": "items", "aggregator": "SUM", "functions": [{"function": "GreaterEq", "args": [500]}]}}
+        ]"#).unwrap();
+
+        assert!(matches!(&conditions[0], ConditionExpr::Single(c) if c.functions[0].function == SingleInputFunction::Greater));
+        assert!(matches!(&conditions[1], ConditionExpr::List(c) if c.constraint.function == ListFunction::AtLeast && c.constraint.args == vec![2]));
+        assert!(matches!(&conditions[2], ConditionExpr::Aggregate(c) if c.aggregator == Aggregator::SUM));
+    }
+}
@@ -1,7 +1,63 @@
-use crate::condition::Condition ;
+use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::condition::{ConditionExpr, ErrorPolicy, LogicalOperator};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Rule {
-    name: String, 
-    conditions: Vec<Condition>,
-    
-}
\ No newline at end of file
+    pub name: String,
+    /// Rules with a higher priority win under the `Priority` hit policy.
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub chain: LogicalOperator,
+    #[serde(default)]
+    pub on_error: ErrorPolicy,
+    pub conditions: Vec<ConditionExpr>,
+    #[serde(default)]
+    pub output: HashMap<String, Value>,
+}
+
+/// Which of the rules of a group that matched make up its output, as in the hit policies of DMN decision tables.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HitPolicy {
+    /// At most one rule may match.
+    Unique,
+    /// The first rule in group order that matches; the rules after it are not evaluated.
+    First,
+    /// The matching rule with the highest priority, the first one of them on a tie.
+    Priority,
+    /// Any number of rules may match, but they must all have the same output.
+    Any,
+    /// The outputs of all matching rules are merged.
+    #[default]
+    Collect,
+    /// Every output path holds the sum of the numbers the matching rules wrote to it.
+    CollectSum,
+    /// Every output path holds the smallest number the matching rules wrote to it.
+    CollectMin,
+    /// Every output path holds the largest number the matching rules wrote to it.
+    CollectMax,
+    /// Every output path holds the number of distinct values the matching rules wrote to it.
+    CollectCount,
+}
+
+/// The rules of one group; all of them are applied to an input evaluated against the group.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleGroup {
+    /// `None` when the group does not set one, so a file adding rules to a group keeps the policy set before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_policy: Option<HitPolicy>,
+    pub rules: Vec<Rule>,
+}
+
+impl RuleGroup {
+    /// The hit policy the group is evaluated with, `Collect` unless it sets one.
+    pub fn hit_policy(&self) -> HitPolicy {
+        self.hit_policy.unwrap_or_default()
+    }
+}
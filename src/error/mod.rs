@@ -0,0 +1,359 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::core::HitPolicy;
+use crate::loader::LoadError;
+use crate::output::MergeConflict;
+use crate::path::PathError;
+
+pub type Result<T> = std::result::Result<T, LogicLoomError>;
+
+/// Where an error happened. Functions fill in `function`, conditions the `input_path` and the engine the `rule`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Location {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+}
+
+impl Location {
+    pub fn function(function: impl fmt::Debug) -> Self {
+        Location { function: Some(format!("{:?}", function)), ..Location::default() }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(rule) = &self.rule {
+            write!(f, "rule [{}]: ", rule)?;
+        }
+        if let Some(input_path) = &self.input_path {
+            write!(f, "path [{}]: ", input_path)?;
+        }
+        if let Some(function) = &self.function {
+            write!(f, "[{}]: ", function)?;
+        }
+        Ok(())
+    }
+}
+
+/// The value an error is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subject {
+    Input,
+    Argument(usize),
+    Element(usize),
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subject::Input => write!(f, "input"),
+            Subject::Argument(i) => write!(f, "argument [{}]", i),
+            Subject::Element(i) => write!(f, "element [{}]", i),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LogicLoomError {
+    /// A value has a different type than the function needs, e.g. a list where a number is expected.
+    TypeMismatch { location: Location, subject: Subject, expected: &'static str, found: &'static str },
+    /// A function or list constraint got the wrong number of arguments.
+    ArgumentCount { location: Location, expected: usize, found: usize },
+    /// A value has the right type but could not be parsed, e.g. the string `"abc"` as a number.
+    Parse { location: Location, message: String },
+    /// A function got arguments it can not work with, e.g. a negative count or a zero denominator.
+    Function { location: Location, message: String },
+    /// An output template could not be rendered, e.g. because it divides by zero or multiplies a string.
+    Template { location: Location, template: Box<str>, message: String },
+    /// The input has nothing at `missing`, the first unresolved prefix of the condition's path.
+    MissingPath { location: Location, missing: String },
+    /// A rule refers to a function that is not registered.
+    UnknownFunction { location: Location, name: String },
+    /// No rules are registered for the group.
+    UnknownGroup { group: String },
+    /// A rule file could not be loaded.
+    RuleLoad(LoadError),
+    /// Rules that were read but fail validation, e.g. a function with the wrong number of arguments; one per problem.
+    InvalidRules(Vec<LoadError>),
+    /// The rules of a group that matched break its hit policy, e.g. two matches in a `Unique` group.
+    HitPolicy { group: String, policy: HitPolicy, message: String },
+    /// Two rules wrote different values to an output path whose merge strategy is `Error`.
+    OutputConflict(Box<MergeConflict>),
+}
+
+impl LogicLoomError {
+    pub fn type_mismatch(function: impl fmt::Debug, subject: Subject, expected: &'static str, found: &Value) -> Self {
+        LogicLoomError::TypeMismatch {
+            location: Location::function(function),
+            subject,
+            expected,
+            found: type_name(found),
+        }
+    }
+
+    pub fn argument_count(function: impl fmt::Debug, expected: usize, found: usize) -> Self {
+        LogicLoomError::ArgumentCount { location: Location::function(function), expected, found }
+    }
+
+    pub fn parse(function: impl fmt::Debug, message: impl Into<String>) -> Self {
+        LogicLoomError::Parse { location: Location::function(function), message: message.into() }
+    }
+
+    pub fn function(function: impl fmt::Debug, message: impl Into<String>) -> Self {
+        LogicLoomError::Function { location: Location::function(function), message: message.into() }
+    }
+
+    pub fn unknown_function(function: impl fmt::Debug) -> Self {
+        let name = format!("{:?}", function);
+        LogicLoomError::UnknownFunction { location: Location::default(), name }
+    }
+
+    /// A short, stable name of the variant, for API clients.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LogicLoomError::TypeMismatch { .. } => "type_mismatch",
+            LogicLoomError::ArgumentCount { .. } => "argument_count",
+            LogicLoomError::Parse { .. } => "parse",
+            LogicLoomError::Function { .. } => "function",
+            LogicLoomError::Template { .. } => "template",
+            LogicLoomError::MissingPath { .. } => "missing_path",
+            LogicLoomError::UnknownFunction { .. } => "unknown_function",
+            LogicLoomError::UnknownGroup { .. } => "unknown_group",
+            LogicLoomError::RuleLoad(_) => "rule_load",
+            LogicLoomError::InvalidRules(_) => "invalid_rules",
+            LogicLoomError::HitPolicy { .. } => "hit_policy",
+            LogicLoomError::OutputConflict(_) => "output_conflict",
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            LogicLoomError::TypeMismatch { location, .. }
+            | LogicLoomError::ArgumentCount { location, .. }
+            | LogicLoomError::Parse { location, .. }
+            | LogicLoomError::Function { location, .. }
+            | LogicLoomError::Template { location, .. }
+            | LogicLoomError::MissingPath { location, .. }
+            | LogicLoomError::UnknownFunction { location, .. } => Some(location),
+            LogicLoomError::UnknownGroup { .. }
+            | LogicLoomError::RuleLoad(_)
+            | LogicLoomError::InvalidRules(_)
+            | LogicLoomError::HitPolicy { .. }
+            | LogicLoomError::OutputConflict(_) => None,
+        }
+    }
+
+    fn location_mut(&mut self) -> Option<&mut Location> {
+        match self {
+            LogicLoomError::TypeMismatch { location, .. }
+            | LogicLoomError::ArgumentCount { location, .. }
+            | LogicLoomError::Parse { location, .. }
+            | LogicLoomError::Function { location, .. }
+            | LogicLoomError::Template { location, .. }
+            | LogicLoomError::MissingPath { location, .. }
+            | LogicLoomError::UnknownFunction { location, .. } => Some(location),
+            LogicLoomError::UnknownGroup { .. }
+            | LogicLoomError::RuleLoad(_)
+            | LogicLoomError::InvalidRules(_)
+            | LogicLoomError::HitPolicy { .. }
+            | LogicLoomError::OutputConflict(_) => None,
+        }
+    }
+
+    /// Sets the rule of the location, unless it is already known.
+    pub fn in_rule(mut self, rule: &str) -> Self {
+        if let Some(location) = self.location_mut() {
+            location.rule.get_or_insert_with(|| rule.to_owned());
+        }
+        self
+    }
+
+    /// Sets the input path of the location, unless it is already known.
+    pub fn at_path(mut self, input_path: &str) -> Self {
+        if let Some(location) = self.location_mut() {
+            location.input_path.get_or_insert_with(|| input_path.to_owned());
+        }
+        self
+    }
+
+    /// Sets the function of the location, unless it is already known.
+    pub fn in_function(mut self, function: impl fmt::Debug) -> Self {
+        if let Some(location) = self.location_mut() {
+            location.function.get_or_insert_with(|| format!("{:?}", function));
+        }
+        self
+    }
+}
+
+impl fmt::Display for LogicLoomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = self.location() {
+            write!(f, "{}", location)?;
+        }
+        match self {
+            LogicLoomError::TypeMismatch { subject, expected, found, .. } => {
+                write!(f, "{} is not a {}, found {}", subject, expected, found)
+            }
+            LogicLoomError::ArgumentCount { expected, found, .. } => {
+                write!(f, "expected {} but got {}", arguments(*expected), found)
+            }
+            LogicLoomError::Parse { message, .. } | LogicLoomError::Function { message, .. } => write!(f, "{}", message),
+            LogicLoomError::Template { template, message, .. } => write!(f, "template [{}]: {}", template, message),
+            LogicLoomError::MissingPath { location, missing } => match &location.input_path {
+                Some(input_path) if input_path != missing => write!(f, "path not found, nothing at [{}]", missing),
+                _ => write!(f, "path not found"),
+            },
+            LogicLoomError::UnknownFunction { name, .. } => write!(f, "unknown function [{}]", name),
+            LogicLoomError::UnknownGroup { group } => write!(f, "no rules registered for group [{}]", group),
+            LogicLoomError::RuleLoad(e) => write!(f, "{}", e),
+            LogicLoomError::InvalidRules(diagnostics) => {
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            LogicLoomError::HitPolicy { group, policy, message } => write!(f, "group [{}] ({:?}): {}", group, policy, message),
+            LogicLoomError::OutputConflict(conflict) => write!(f, "{}", conflict),
+        }
+    }
+}
+
+impl Error for LogicLoomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LogicLoomError::RuleLoad(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes as `{"code": ..., "message": ..., "location": ...}` for API clients.
+impl Serialize for LogicLoomError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let location = self.location().filter(|location| **location != Location::default());
+        let mut state = serializer.serialize_struct("LogicLoomError", 2 + location.is_some() as usize)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let Some(location) = location {
+            state.serialize_field("location", location)?;
+        }
+        state.end()
+    }
+}
+
+impl From<LoadError> for LogicLoomError {
+    fn from(e: LoadError) -> Self {
+        LogicLoomError::RuleLoad(e)
+    }
+}
+
+impl From<PathError> for LogicLoomError {
+    fn from(e: PathError) -> Self {
+        match e {
+            PathError::Invalid { path, message } => LogicLoomError::Parse {
+                location: Location { input_path: Some(path.clone()), ..Location::default() },
+                message: format!("invalid path [{}]: {}", path, message),
+            },
+            PathError::NotFound { path, missing } => LogicLoomError::MissingPath {
+                location: Location { input_path: Some(path), ..Location::default() },
+                missing,
+            },
+            PathError::NotAList { path } => LogicLoomError::TypeMismatch {
+                location: Location { input_path: Some(path), ..Location::default() },
+                subject: Subject::Input,
+                expected: "list",
+                found: "single value",
+            },
+        }
+    }
+}
+
+/// `n` arguments, in the singular for one.
+pub(crate) fn arguments(n: usize) -> String {
+    match n {
+        1 => "1 argument".to_owned(),
+        n => format!("{} arguments", n),
+    }
+}
+
+/// The JSON type of a value, as used in error messages.
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "list",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::list_function::ListFunction;
+    use crate::function::SingleInputFunction;
+    use serde_json::json;
+
+    #[test]
+    fn error_location_is_filled_from_the_inside_out() {
+        let err = LogicLoomError::type_mismatch(SingleInputFunction::Greater, Subject::Input, "number", &json!([1]))
+            .at_path("customer.age")
+            .in_rule("adult")
+            .in_rule("outer");
+
+        assert_eq!(err.code(), "type_mismatch");
+        assert_eq!(err.location(), Some(&Location {
+            rule: Some("adult".to_owned()),
+            input_path: Some("customer.age".to_owned()),
+            function: Some("Greater".to_owned()),
+        }));
+        assert_eq!(err.to_string(), "rule [adult]: path [customer.age]: [Greater]: input is not a number, found list");
+    }
+
+    #[test]
+    fn serialize_error() {
+        let err = LogicLoomError::argument_count(SingleInputFunction::Between, 2, 1).in_rule("adult");
+        assert_eq!(serde_json::to_value(&err).unwrap(), json!({
+            "code": "argument_count",
+            "message": "rule [adult]: [Between]: expected 2 arguments but got 1",
+            "location": {"rule": "adult", "function": "Between"},
+        }));
+
+        let err = LogicLoomError::function(ListFunction::AtMostFraction, "the denominator is 0").at_path("items");
+        assert_eq!(serde_json::to_value(&err).unwrap(), json!({
+            "code": "function",
+            "message": "path [items]: [AtMostFraction]: the denominator is 0",
+            "location": {"input_path": "items", "function": "AtMostFraction"},
+        }));
+
+        let err = LogicLoomError::UnknownGroup { group: "pricing".to_owned() };
+        assert_eq!(serde_json::to_value(&err).unwrap(), json!({
+            "code": "unknown_group",
+            "message": "no rules registered for group [pricing]",
+        }));
+    }
+
+    #[test]
+    fn path_errors_convert_to_missing_path() {
+        let err: LogicLoomError = PathError::NotFound {
+            path: "customer.address.country".to_owned(),
+            missing: "customer.address".to_owned(),
+        }.into();
+
+        assert_eq!(err.code(), "missing_path");
+        assert_eq!(err.to_string(), "path [customer.address.country]: path not found, nothing at [customer.address]");
+    }
+}
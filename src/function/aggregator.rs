@@ -1,65 +1,96 @@
 use std::collections::HashMap;
-use std::error::Error;
-use std::any::Any;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Eq, PartialEq, Hash)]
+use super::parse_number;
+use crate::error::{LogicLoomError, Result, Subject};
+
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Aggregator {
+    #[serde(alias = "sum")]
     SUM,
+    #[serde(alias = "min")]
     MIN,
+    #[serde(alias = "max")]
     MAX,
+    #[serde(alias = "avg")]
     AVG,
+    #[serde(alias = "count")]
     COUNT,
 }
 
-fn parse_inputs_to_numbers(inputs: &[Box<dyn Any>]) -> Result<Vec<f64>, Box<dyn Error>> {
+fn parse_inputs_to_numbers(aggregator: Aggregator, inputs: &[&Value]) -> Result<Vec<f64>> {
     if inputs.is_empty() {
-        return Err("no inputs provided".into());
+        return Err(LogicLoomError::parse(aggregator, "no inputs provided"));
     }
 
     let mut inputs_nr = Vec::new();
-    for input in inputs {
-        let input_str = input.downcast_ref::<String>()
-            .ok_or_else(|| "input is not a string")?;
-        let input_nr = input_str.parse::<f64>()
-            .map_err(|err| format!("could not convert input [{}] to number: {}", input_str, err))?;
-        inputs_nr.push(input_nr);
+    for (i, input) in inputs.iter().enumerate() {
+        inputs_nr.push(parse_number(aggregator, Subject::Element(i), input)?);
     }
 
     Ok(inputs_nr)
 }
 
-pub type AggregateFunction = Box<dyn Fn(Vec<Box<dyn Any>>) -> Result<f64, Box<dyn Error>>>;
+pub type AggregateFunction = Box<dyn Fn(&[&Value]) -> Result<f64> + Send + Sync>;
 
-fn default_aggregate_function() -> HashMap<Aggregator, AggregateFunction> {
+pub fn default_aggregate_function() -> HashMap<Aggregator, AggregateFunction> {
     let mut m: HashMap< Aggregator, AggregateFunction>  = HashMap::new();
 
-    m.insert(Aggregator::SUM, Box::new(|input: Vec<Box<dyn Any>>| {
-        let inputs_as_numbers = parse_inputs_to_numbers(&input)?;
+    m.insert(Aggregator::SUM, Box::new(|input: &[&Value]| {
+        if input.is_empty() {
+            return Ok(0.0);
+        }
+        let inputs_as_numbers = parse_inputs_to_numbers(Aggregator::SUM, input)?;
         let sum: f64 = inputs_as_numbers.iter().sum();
         Ok(sum)
 
     }));
 
-    m.insert(Aggregator::AVG, Box::new(|input: Vec<Box<dyn Any>>| {
-        let inputs_as_numbers = parse_inputs_to_numbers(&input)?;
+    m.insert(Aggregator::AVG, Box::new(|input: &[&Value]| {
+        let inputs_as_numbers = parse_inputs_to_numbers(Aggregator::AVG, input)?;
         let sum: f64 = inputs_as_numbers.iter().sum();
         Ok( sum / input.len() as f64)
     }));
 
-    m.insert(Aggregator::MAX, Box::new(|input: Vec<Box<dyn Any>>| {
-        let inputs_as_numbers = parse_inputs_to_numbers(&input)?;
+    m.insert(Aggregator::MAX, Box::new(|input: &[&Value]| {
+        let inputs_as_numbers = parse_inputs_to_numbers(Aggregator::MAX, input)?;
         let mx = inputs_as_numbers.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
         Ok(mx)
     }));
 
-    m.insert(Aggregator::MIN, Box::new(|input: Vec<Box<dyn Any>>| {
-        let inputs_as_numbers = parse_inputs_to_numbers(&input)?;
-        let mn = inputs_as_numbers.iter().fold(f64::NEG_INFINITY, |a, &b| a.min(b));
+    m.insert(Aggregator::MIN, Box::new(|input: &[&Value]| {
+        let inputs_as_numbers = parse_inputs_to_numbers(Aggregator::MIN, input)?;
+        let mn = inputs_as_numbers.iter().fold(f64::INFINITY, |a, &b| a.min(b));
         Ok(mn)
     }));
 
-    m.insert(Aggregator::COUNT, Box::new(|input: Vec<Box<dyn Any>>| {
+    m.insert(Aggregator::COUNT, Box::new(|input: &[&Value]| {
       Ok(input.len() as f64)
     }));
      m
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_default_aggregate_functions() {
+        let functions = default_aggregate_function();
+        let values = [json!(3), json!("1.5"), json!(7)];
+        let input: Vec<&Value> = values.iter().collect();
+
+        assert_eq!(functions[&Aggregator::SUM](&input).unwrap(), 11.5);
+        assert_eq!(functions[&Aggregator::MIN](&input).unwrap(), 1.5);
+        assert_eq!(functions[&Aggregator::MAX](&input).unwrap(), 7.0);
+        assert_eq!(functions[&Aggregator::AVG](&input).unwrap(), 11.5 / 3.0);
+        assert_eq!(functions[&Aggregator::COUNT](&input).unwrap(), 3.0);
+
+        assert_eq!(functions[&Aggregator::SUM](&[]).unwrap(), 0.0);
+        assert_eq!(functions[&Aggregator::COUNT](&[]).unwrap(), 0.0);
+        assert!(functions[&Aggregator::MIN](&[]).is_err());
+        assert!(functions[&Aggregator::SUM](&[&json!("abc")]).is_err());
+    }
+}
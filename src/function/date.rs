@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::{DateTime, Datelike, Days, LocalResult, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{arg_str, check_args, Function, SingleInputFunction};
+use crate::error::{LogicLoomError, Result, Subject};
+
+/// Where the date functions read the current time from.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The time of the system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that always reads the same time, so that rules relative to now can be tested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// How the date functions read dates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateOptions {
+    /// The timezone of dates and of times without an offset, and the one days of the week and hours are read in.
+    #[serde(default = "utc")]
+    pub timezone: Tz,
+    /// `chrono` formats tried after RFC 3339 and ISO 8601, e.g. `%d.%m.%Y`.
+    #[serde(default)]
+    pub formats: Vec<String>,
+}
+
+fn utc() -> Tz {
+    Tz::UTC
+}
+
+impl Default for DateOptions {
+    fn default() -> Self {
+        DateOptions { timezone: Tz::UTC, formats: Vec::new() }
+    }
+}
+
+// A parsed date or time, and whether it was a date without a time, which stands for the whole day.
+#[derive(Debug, Clone, Copy)]
+struct Moment {
+    at: DateTime<Tz>,
+    date_only: bool,
+}
+
+const ISO_8601_LOCAL: [&str; 3] = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M"];
+
+impl DateOptions {
+    /// Reads an RFC 3339 timestamp, an ISO 8601 date and time without offset, an ISO 8601 date, or a string in one of
+    /// `formats`. A date is read as the start of the day.
+    pub fn parse(&self, s: &str) -> Option<DateTime<Tz>> {
+        self.moment(s).map(|moment| moment.at)
+    }
+
+    fn moment(&self, s: &str) -> Option<Moment> {
+        let s = s.trim();
+        if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+            return Some(Moment { at: at.with_timezone(&self.timezone), date_only: false });
+        }
+        if let Some(at) = ISO_8601_LOCAL.iter().find_map(|format| NaiveDateTime::parse_from_str(s, format).ok()) {
+            return self.local(at, false);
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return self.local(date.and_time(NaiveTime::MIN), true);
+        }
+        self.formats.iter().find_map(|format| {
+            if let Ok(at) = DateTime::parse_from_str(s, format) {
+                return Some(Moment { at: at.with_timezone(&self.timezone), date_only: false });
+            }
+            if let Ok(at) = NaiveDateTime::parse_from_str(s, format) {
+                return self.local(at, false);
+            }
+            let date = NaiveDate::parse_from_str(s, format).ok()?;
+            self.local(date.and_time(NaiveTime::MIN), true)
+        })
+    }
+
+    // A local time in `timezone`. A time skipped by a change to daylight saving time is read an hour later, a time
+    // repeated by a change back as the first of the two.
+    fn local(&self, at: NaiveDateTime, date_only: bool) -> Option<Moment> {
+        let at = match self.timezone.from_local_datetime(&at) {
+            LocalResult::None => self.timezone.from_local_datetime(&(at + TimeDelta::hours(1))).earliest(),
+            found => found.earliest(),
+        }?;
+        Some(Moment { at, date_only })
+    }
+}
+
+/// A length of time. Days and longer are calendar units, so a day is 23 hours on the day daylight saving time starts
+/// and `1 month` before March 31st is February 28th or 29th.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Span {
+    Seconds(i64),
+    Days(u64),
+    Months(u32),
+}
+
+impl Span {
+    /// Reads a number followed by a unit, e.g. `30d`, `30 days`, `18y` or `90 min`. The units are `s`, `m` or `min`,
+    /// `h`, `d`, `w`, `mo` and `y`, also written out in the singular or plural.
+    pub fn parse(s: &str) -> Option<Span> {
+        let s = s.trim();
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let n: u32 = s[..digits].parse().ok()?;
+        let span = match s[digits..].trim_start().to_lowercase().as_str() {
+            "s" | "sec" | "secs" | "second" | "seconds" => Span::Seconds(n as i64),
+            "m" | "min" | "mins" | "minute" | "minutes" => Span::Seconds(n as i64 * 60),
+            "h" | "hr" | "hrs" | "hour" | "hours" => Span::Seconds(n as i64 * 3600),
+            "d" | "day" | "days" => Span::Days(n as u64),
+            "w" | "week" | "weeks" => Span::Days(n as u64 * 7),
+            "mo" | "month" | "months" => Span::Months(n),
+            "y" | "yr" | "yrs" | "year" | "years" => Span::Months(n.checked_mul(12)?),
+            _ => return None,
+        };
+        Some(span)
+    }
+
+    // The time `self` before `at`, `None` before the earliest date `chrono` represents.
+    fn before(self, at: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        match self {
+            Span::Seconds(seconds) => at.checked_sub_signed(TimeDelta::seconds(seconds)),
+            Span::Days(days) => at.checked_sub_days(Days::new(days)),
+            Span::Months(months) => at.checked_sub_months(Months::new(months)),
+        }
+    }
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    let s = s.trim();
+    NaiveTime::parse_from_str(s, "%H:%M").or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S")).ok()
+}
+
+/// Why an argument of a date function can not be read, with dates read as `options` read them.
+pub fn invalid_argument(function: SingleInputFunction, options: &DateOptions, arg: &str) -> Option<String> {
+    let (valid, kind) = match function {
+        SingleInputFunction::Before | SingleInputFunction::After | SingleInputFunction::BetweenDates => {
+            (options.parse(arg).is_some(), "a date")
+        }
+        SingleInputFunction::WithinLast | SingleInputFunction::OlderThan => (Span::parse(arg).is_some(), "a duration"),
+        SingleInputFunction::DayOfWeek => (arg.parse::<Weekday>().is_ok(), "a day of the week"),
+        SingleInputFunction::BusinessHours => (parse_time(arg).is_some(), "a time of day"),
+        _ => return None,
+    };
+    match valid {
+        true => None,
+        false => Some(format!("`{:?}` takes {} but got [\"{}\"]", function, kind, arg)),
+    }
+}
+
+/// Why the arguments of a date function do not go together: `BusinessHours` opening at or after its closing time.
+pub fn invalid_arguments(function: SingleInputFunction, args: &[Value]) -> Option<String> {
+    if function != SingleInputFunction::BusinessHours || args.len() != 2 {
+        return None;
+    }
+    let (opens, closes) = (parse_time(args[0].as_str()?)?, parse_time(args[1].as_str()?)?);
+    (opens >= closes).then(|| format!("`{:?}` has an opening time {} not before its closing time {}", function, args[0], args[1]))
+}
+
+// Like `parse_number`: a value of the wrong type is told apart from a string that is not a date.
+fn moment(function: SingleInputFunction, options: &DateOptions, subject: Subject, value: &Value) -> Result<Moment> {
+    let s = value.as_str().ok_or_else(|| LogicLoomError::type_mismatch(function, subject, "string", value))?;
+    options.moment(s).ok_or_else(|| LogicLoomError::parse(function, format!("could not convert {} [{}] to a date", subject, value)))
+}
+
+fn span(function: SingleInputFunction, args: &[Value]) -> Result<Span> {
+    let arg = arg_str(function, args, 0)?;
+    Span::parse(arg).ok_or_else(|| LogicLoomError::parse(function, format!("could not convert {} [{}] to a duration", Subject::Argument(0), args[0])))
+}
+
+fn time(function: SingleInputFunction, args: &[Value], i: usize) -> Result<NaiveTime> {
+    let arg = arg_str(function, args, i)?;
+    parse_time(arg).ok_or_else(|| LogicLoomError::parse(function, format!("could not convert {} [{}] to a time of day", Subject::Argument(i), args[i])))
+}
+
+// Whether the input lies within the bounds, both included. A date as upper bound includes the whole day, and a `null`
+// bound leaves the range open on that side.
+fn within(function: SingleInputFunction, options: &DateOptions, input: DateTime<Tz>, args: &[Value]) -> Result<bool> {
+    let bound = |i: usize| match &args[i] {
+        Value::Null => Ok(None),
+        arg => moment(function, options, Subject::Argument(i), arg).map(Some),
+    };
+    let after_start = match bound(0)? {
+        Some(start) => input >= start.at,
+        None => true,
+    };
+    let before_end = match bound(1)? {
+        Some(Moment { at, date_only: true }) => at.checked_add_days(Days::new(1)).is_none_or(|next_day| input < next_day),
+        Some(Moment { at, date_only: false }) => input <= at,
+        None => true,
+    };
+    Ok(after_start && before_end)
+}
+
+/// The date functions. Their input is a date in a format `options` read, and "now" is the time of `clock`.
+pub fn default_date_functions(m: &mut HashMap<SingleInputFunction, Function>, options: &DateOptions, clock: &Arc<dyn Clock>) {
+    let options = Arc::new(options.clone());
+
+    let shared = options.clone();
+    m.insert(SingleInputFunction::Before, Box::new(move |input, args| {
+        check_args(SingleInputFunction::Before, args, 1)?;
+        let input = moment(SingleInputFunction::Before, &shared, Subject::Input, input)?;
+        Ok(input.at < moment(SingleInputFunction::Before, &shared, Subject::Argument(0), &args[0])?.at)
+    }));
+
+    let shared = options.clone();
+    m.insert(SingleInputFunction::After, Box::new(move |input, args| {
+        check_args(SingleInputFunction::After, args, 1)?;
+        let input = moment(SingleInputFunction::After, &shared, Subject::Input, input)?;
+        Ok(input.at > moment(SingleInputFunction::After, &shared, Subject::Argument(0), &args[0])?.at)
+    }));
+
+    let shared = options.clone();
+    m.insert(SingleInputFunction::BetweenDates, Box::new(move |input, args| {
+        check_args(SingleInputFunction::BetweenDates, args, 2)?;
+        let input = moment(SingleInputFunction::BetweenDates, &shared, Subject::Input, input)?;
+        within(SingleInputFunction::BetweenDates, &shared, input.at, args)
+    }));
+
+    let (shared, shared_clock) = (options.clone(), clock.clone());
+    m.insert(SingleInputFunction::WithinLast, Box::new(move |input, args| {
+        check_args(SingleInputFunction::WithinLast, args, 1)?;
+        let input = moment(SingleInputFunction::WithinLast, &shared, Subject::Input, input)?;
+        let now = shared_clock.now().with_timezone(&shared.timezone);
+        let since = span(SingleInputFunction::WithinLast, args)?.before(now);
+        Ok(since.is_none_or(|since| since <= input.at) && input.at <= now)
+    }));
+
+    let (shared, shared_clock) = (options.clone(), clock.clone());
+    m.insert(SingleInputFunction::OlderThan, Box::new(move |input, args| {
+        check_args(SingleInputFunction::OlderThan, args, 1)?;
+        let input = moment(SingleInputFunction::OlderThan, &shared, Subject::Input, input)?;
+        let now = shared_clock.now().with_timezone(&shared.timezone);
+        let until = span(SingleInputFunction::OlderThan, args)?.before(now);
+        Ok(until.is_some_and(|until| input.at <= until))
+    }));
+
+    let shared = options.clone();
+    m.insert(SingleInputFunction::DayOfWeek, Box::new(move |input, args| {
+        let input = moment(SingleInputFunction::DayOfWeek, &shared, Subject::Input, input)?.at.weekday();
+        for i in 0..args.len() {
+            let day = arg_str(SingleInputFunction::DayOfWeek, args, i)?.parse::<Weekday>().map_err(|_| {
+                LogicLoomError::parse(SingleInputFunction::DayOfWeek, format!("could not convert {} [{}] to a day of the week", Subject::Argument(i), args[i]))
+            })?;
+            if day == input {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }));
+
+    let shared = options;
+    m.insert(SingleInputFunction::BusinessHours, Box::new(move |input, args| {
+        check_args(SingleInputFunction::BusinessHours, args, 2)?;
+        let input = moment(SingleInputFunction::BusinessHours, &shared, Subject::Input, input)?.at;
+        let (opens, closes) = (time(SingleInputFunction::BusinessHours, args, 0)?, time(SingleInputFunction::BusinessHours, args, 1)?);
+        if opens >= closes {
+            return Err(LogicLoomError::parse(SingleInputFunction::BusinessHours,
+                format!("the opening time [{}] is not before the closing time [{}]", args[0], args[1])));
+        }
+        let weekday = !matches!(input.weekday(), Weekday::Sat | Weekday::Sun);
+        Ok(weekday && opens <= input.time() && input.time() < closes)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Saturday, June 15th 2024, 10:30 UTC.
+    fn now() -> DateTime<Utc> {
+        "2024-06-15T10:30:00Z".parse().unwrap()
+    }
+
+    fn functions(options: DateOptions) -> HashMap<SingleInputFunction, Function> {
+        let mut m = HashMap::new();
+        let clock: Arc<dyn Clock> = Arc::new(FixedClock(now()));
+        default_date_functions(&mut m, &options, &clock);
+        m
+    }
+
+    fn check(functions: &HashMap<SingleInputFunction, Function>, function: SingleInputFunction, args: Value, cases: &[(&str, bool)]) {
+        let args = args.as_array().unwrap();
+        for (input, expected) in cases {
+            assert_eq!(functions[&function](&json!(input), args).unwrap(), *expected, "{:?} {} {:?}", function, input, args);
+        }
+    }
+
+    #[test]
+    fn parse_dates() {
+        let options = DateOptions { timezone: Tz::Europe__Berlin, formats: vec!["%d.%m.%Y".to_owned(), "%d.%m.%Y %H:%M".to_owned()] };
+        let parsed = |s: &str| options.parse(s).map(|at| at.to_rfc3339());
+
+        assert_eq!(parsed("2024-06-15T10:30:00Z").unwrap(), "2024-06-15T12:30:00+02:00");
+        assert_eq!(parsed("2024-06-15T10:30:00.250-04:00").unwrap(), "2024-06-15T16:30:00.250+02:00");
+        assert_eq!(parsed("2024-06-15T10:30:00").unwrap(), "2024-06-15T10:30:00+02:00");
+        assert_eq!(parsed("2024-06-15 10:30:00").unwrap(), "2024-06-15T10:30:00+02:00");
+        assert_eq!(parsed("2024-01-15").unwrap(), "2024-01-15T00:00:00+01:00");
+        assert_eq!(parsed("15.01.2024").unwrap(), "2024-01-15T00:00:00+01:00");
+        assert_eq!(parsed("15.01.2024 08:00").unwrap(), "2024-01-15T08:00:00+01:00");
+        // 02:30 does not exist on the day daylight saving time starts.
+        assert_eq!(parsed("2024-03-31T02:30:00").unwrap(), "2024-03-31T03:30:00+02:00");
+        assert!(parsed("yesterday").is_none());
+        assert!(parsed("2024-02-30").is_none());
+    }
+
+    #[test]
+    fn compare_dates() {
+        use SingleInputFunction::*;
+        let f = functions(DateOptions::default());
+
+        check(&f, Before, json!(["2024-01-01"]), &[("2023-12-31T23:59:59Z", true), ("2024-01-01", false), ("2024-01-01T00:00:01+00:00", false)]);
+        check(&f, After, json!(["2024-01-01T12:00:00+02:00"]), &[("2024-01-01T10:00:01Z", true), ("2024-01-01T10:00:00Z", false)]);
+        check(&f, BetweenDates, json!(["2024-06-01", "2024-06-30"]), &[
+            ("2024-05-31T23:59:59Z", false),
+            ("2024-06-01", true),
+            ("2024-06-30T23:59:59Z", true),
+            ("2024-07-01", false),
+        ]);
+        check(&f, BetweenDates, json!(["2024-06-01T09:00:00Z", "2024-06-30T18:00:00Z"]), &[("2024-06-30T18:00:00Z", true), ("2024-06-30T18:00:01Z", false)]);
+        check(&f, BetweenDates, json!([null, "2024-06-30"]), &[("1900-01-01", true), ("2024-07-01", false)]);
+        check(&f, BetweenDates, json!(["2024-06-01", null]), &[("2024-05-31", false), ("9999-12-31", true)]);
+    }
+
+    #[test]
+    fn relative_to_now() {
+        use SingleInputFunction::*;
+        let f = functions(DateOptions::default());
+
+        check(&f, WithinLast, json!(["30d"]), &[
+            ("2024-05-16T10:30:00Z", true),
+            ("2024-05-16T10:29:59Z", false),
+            ("2024-06-15T10:30:00Z", true),
+            ("2024-06-15T10:30:01Z", false),
+        ]);
+        check(&f, WithinLast, json!(["2 hours"]), &[("2024-06-15T08:30:00Z", true), ("2024-06-15T08:29:00Z", false)]);
+        check(&f, WithinLast, json!(["1 mo"]), &[("2024-05-15T10:30:00Z", true), ("2024-05-15", false)]);
+        // Born on June 15th 2006, 18 years old today.
+        check(&f, OlderThan, json!(["18y"]), &[("2006-06-15", true), ("2006-06-16", false), ("1950-01-01", true)]);
+        check(&f, OlderThan, json!(["18 years"]), &[("2006-06-15T10:30:00Z", true), ("2006-06-15T10:30:01Z", false)]);
+        check(&f, OlderThan, json!(["1w"]), &[("2024-06-08T10:30:00Z", true), ("2024-06-09", false)]);
+    }
+
+    #[test]
+    fn days_and_hours() {
+        use SingleInputFunction::*;
+        let f = functions(DateOptions::default());
+
+        check(&f, DayOfWeek, json!(["Sat", "sunday"]), &[("2024-06-15", true), ("2024-06-16T23:00:00Z", true), ("2024-06-17", false)]);
+        check(&f, BusinessHours, json!(["09:00", "17:30"]), &[
+            ("2024-06-14T09:00:00Z", true),
+            ("2024-06-14T17:29:59Z", true),
+            ("2024-06-14T17:30:00Z", false),
+            ("2024-06-14T08:59:00Z", false),
+            ("2024-06-15T12:00:00Z", false),
+        ]);
+    }
+
+    #[test]
+    fn timezones() {
+        use SingleInputFunction::*;
+        let f = functions(DateOptions { timezone: Tz::America__New_York, formats: Vec::new() });
+
+        // Late on Friday in New York is Saturday in UTC.
+        check(&f, DayOfWeek, json!(["Fri"]), &[("2024-06-15T02:00:00Z", true)]);
+        check(&f, BusinessHours, json!(["09:00", "17:00"]), &[("2024-06-14T14:00:00Z", true), ("2024-06-14T12:00:00Z", false), ("2024-06-14T12:00:00+04:00", false)]);
+        // Dates and times without an offset are in New York.
+        check(&f, Before, json!(["2024-06-15T10:30:00Z"]), &[("2024-06-15T06:29:00", true), ("2024-06-15T06:31:00", false)]);
+        check(&f, WithinLast, json!(["1d"]), &[("2024-06-14", false), ("2024-06-14T06:30:00", true)]);
+    }
+
+    #[test]
+    fn errors() {
+        let f = functions(DateOptions::default());
+        let error = |function: SingleInputFunction, input: Value, args: Value| {
+            f[&function](&input, args.as_array().unwrap()).unwrap_err().to_string()
+        };
+
+        assert_eq!(error(SingleInputFunction::Before, json!(20240101), json!(["2024-01-01"])), "[Before]: input is not a string, found number");
+        assert_eq!(error(SingleInputFunction::After, json!("01/02/2024"), json!(["2024-01-01"])), "[After]: could not convert input [\"01/02/2024\"] to a date");
+        assert_eq!(error(SingleInputFunction::BetweenDates, json!("2024-01-01"), json!(["2024-01-01"])), "[BetweenDates]: expected 2 arguments but got 1");
+        assert_eq!(error(SingleInputFunction::WithinLast, json!("2024-01-01"), json!(["30 fortnights"])), "[WithinLast]: could not convert argument [0] [\"30 fortnights\"] to a duration");
+        assert_eq!(error(SingleInputFunction::DayOfWeek, json!("2024-01-01"), json!(["Tue", "Moonday"])), "[DayOfWeek]: could not convert argument [1] [\"Moonday\"] to a day of the week");
+        assert_eq!(error(SingleInputFunction::BusinessHours, json!("2024-01-01"), json!(["17:00", "9:00"])),
+            "[BusinessHours]: the opening time [\"17:00\"] is not before the closing time [\"9:00\"]");
+
+        let options = DateOptions::default();
+        assert_eq!(invalid_argument(SingleInputFunction::OlderThan, &options, "18 yaers").unwrap(), "`OlderThan` takes a duration but got [\"18 yaers\"]");
+        assert_eq!(invalid_argument(SingleInputFunction::BusinessHours, &options, "9:00"), None);
+        assert_eq!(invalid_argument(SingleInputFunction::Before, &options, "01.02.2024").unwrap(), "`Before` takes a date but got [\"01.02.2024\"]");
+        let options = DateOptions { formats: vec!["%d.%m.%Y".to_owned()], ..DateOptions::default() };
+        assert_eq!(invalid_argument(SingleInputFunction::Before, &options, "01.02.2024"), None);
+        assert_eq!(invalid_argument(SingleInputFunction::Contains, &options, "anything"), None);
+
+        assert_eq!(invalid_arguments(SingleInputFunction::BusinessHours, &[json!("17:00"), json!("9:00")]).unwrap(),
+            "`BusinessHours` has an opening time \"17:00\" not before its closing time \"9:00\"");
+        assert_eq!(invalid_arguments(SingleInputFunction::BusinessHours, &[json!("9:00"), json!("17:00")]), None);
+    }
+}
@@ -1,21 +1,29 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
 
-#[derive(Eq, PartialEq, Hash)]
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum ListFunction {
+    #[serde(alias = "all")]
     All,
-    AtLeast, 
+    #[serde(alias = "at_least")]
+    AtLeast,
+    #[serde(alias = "at_most")]
     AtMost,
-    Exactly, 
+    #[serde(alias = "exactly")]
+    Exactly,
+    #[serde(alias = "none")]
     NoneElement,
+    #[serde(alias = "at_least_fraction")]
     AtLeastFraction,
+    #[serde(alias = "at_most_fraction")]
     AtMostFraction
 }
 
-pub type ListFunctionConstraint = Box<dyn Fn(usize, usize, &[i32]) -> bool>;
+pub type ListFunctionConstraint = Box<dyn Fn(usize, usize, &[i32]) -> bool + Send + Sync>;
 
-fn ListFunctionConstraintsArgumentNumber(constraintName: ListFunction) -> i32 {
-    match constraintName {
+pub fn list_function_constraints_argument_number(constraint_name: ListFunction) -> i32 {
+    match constraint_name {
         ListFunction::All => 0,
         ListFunction::AtLeast => 1,
         ListFunction::AtMost => 1,
@@ -26,7 +34,7 @@ fn ListFunctionConstraintsArgumentNumber(constraintName: ListFunction) -> i32 {
     }
 }
 
-fn default_list_function() -> HashMap<ListFunction, ListFunctionConstraint> {
+pub fn default_list_function() -> HashMap<ListFunction, ListFunctionConstraint> {
     let mut m: HashMap< ListFunction, ListFunctionConstraint> = HashMap::new();
     m.insert(ListFunction::All, Box::new(|list_total, passed_total, _args| {
         passed_total == list_total
@@ -1,398 +1,508 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::error::Error;
-use std::any::Any;
-use std::io;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use aggregator::{default_aggregate_function, AggregateFunction, Aggregator};
+use date::{default_date_functions, Clock, DateOptions, SystemClock};
+use list_function::{default_list_function, ListFunction, ListFunctionConstraint};
+use numeric::{compare, default_numeric_functions, numbers_equal, Number, DEFAULT_EPSILON};
+use pattern::{default_pattern_functions, Patterns};
+use signature::{default_signatures, FunctionKind, Signature, Signatures};
+use text::{default_text_functions, TextOptions};
+
+use crate::error::{LogicLoomError, Result, Subject};
 
 pub  mod list_function;
 pub mod aggregator;
+pub mod date;
+pub mod numeric;
+pub mod pattern;
+pub mod signature;
+pub mod text;
 
-#[derive(Hash, Eq, PartialEq, Debug)]
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum SingleInputFunction {
-   Empty, 
-   NonEmpty,
-   Equal,
-   Greater, 
-   GreaterEq, 
-   Lower,
-   LowerEq,
-   Between,
-   BetweenEq,
-   NotBetween,
-   NotBetweenEq,
-   EqualIgnoreCase,
-   EqualAny,
-   EqualAnyIgnoreCase,
-   NotEqualAny,
-   StartsWith,
-   StartsWithIgnoreCase,
-   EndsWith,
-   EndsWithIgnoreCase,
-   Contains,
-   ContainsIgnoreCase
+    #[serde(alias = "empty")]
+    Empty,
+    #[serde(alias = "non_empty")]
+    NonEmpty,
+    #[serde(alias = "equal", alias = "eq", alias = "==")]
+    Equal,
+    #[serde(alias = "not_equal", alias = "ne", alias = "!=")]
+    NotEqual,
+    #[serde(alias = "greater", alias = "gt", alias = ">")]
+    Greater,
+    #[serde(alias = "greater_eq", alias = "gte", alias = ">=")]
+    GreaterEq,
+    #[serde(alias = "lower", alias = "lt", alias = "<")]
+    Lower,
+    #[serde(alias = "lower_eq", alias = "lte", alias = "<=")]
+    LowerEq,
+    #[serde(alias = "between")]
+    Between,
+    #[serde(alias = "between_eq")]
+    BetweenEq,
+    #[serde(alias = "not_between")]
+    NotBetween,
+    #[serde(alias = "not_between_eq")]
+    NotBetweenEq,
+    #[serde(alias = "equal_ignore_case")]
+    EqualIgnoreCase,
+    #[serde(alias = "equal_any", alias = "in")]
+    EqualAny,
+    #[serde(alias = "equal_any_ignore_case")]
+    EqualAnyIgnoreCase,
+    #[serde(alias = "not_equal_any", alias = "not_in")]
+    NotEqualAny,
+    #[serde(alias = "starts_with")]
+    StartsWith,
+    #[serde(alias = "starts_with_ignore_case")]
+    StartsWithIgnoreCase,
+    #[serde(alias = "ends_with")]
+    EndsWith,
+    #[serde(alias = "ends_with_ignore_case")]
+    EndsWithIgnoreCase,
+    #[serde(alias = "contains")]
+    Contains,
+    #[serde(alias = "contains_ignore_case")]
+    ContainsIgnoreCase,
+    #[serde(alias = "matches", alias = "~")]
+    Matches,
+    #[serde(alias = "not_matches", alias = "!~")]
+    NotMatches,
+    #[serde(alias = "matches_any")]
+    MatchesAny,
+    #[serde(alias = "before")]
+    Before,
+    #[serde(alias = "after")]
+    After,
+    #[serde(alias = "between_dates")]
+    BetweenDates,
+    #[serde(alias = "within_last")]
+    WithinLast,
+    #[serde(alias = "older_than")]
+    OlderThan,
+    #[serde(alias = "day_of_week")]
+    DayOfWeek,
+    #[serde(alias = "business_hours")]
+    BusinessHours,
+    #[serde(alias = "exists")]
+    Exists,
+    #[serde(alias = "is_null")]
+    IsNull,
+    #[serde(alias = "is_number")]
+    IsNumber,
+    #[serde(alias = "is_boolean")]
+    IsBoolean,
+    #[serde(alias = "is_list")]
+    IsList,
 }
 
-pub type Function = Box<dyn Fn(Box<dyn Any>, Vec<Box<dyn Any>>) -> Result<bool, Box<dyn Error>> + 'static>;
+pub type Function = Box<dyn Fn(&Value, &[Value]) -> Result<bool> + Send + Sync + 'static>;
 
-fn default() -> HashMap<SingleInputFunction, Function> {
-    let mut map: HashMap<SingleInputFunction, Function>  = HashMap::new();
-    default_general_functions(&mut map);
-    default_numeric_function(&mut map);
-    default_string_function(&mut map);
-    map
+/// A function applied to a condition's input, as written in a rule file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FunctionCall {
+    pub function: SingleInputFunction,
+    #[serde(default)]
+    pub args: Vec<Value>,
 }
 
-fn default_general_functions(m : &mut HashMap<SingleInputFunction, Function>) -> &mut HashMap<SingleInputFunction, Function> {
-   // let mut m = HashMap::new();
-    
-    m.insert(SingleInputFunction::Empty, Box::new(|input: Box<dyn Any>, _args: Vec<Box<dyn Any>>| {
-        match input.downcast_ref::<String>() {
-            Some(string) => Ok(string.is_empty()),
-            None => Err(Box::new(io::Error::new(io::ErrorKind::Other, "Input is not a string")) as Box<dyn Error>),
-        }
-    })as Function);
-
-    m.insert(SingleInputFunction::NonEmpty, Box::new(|input: Box<dyn Any>, _args: Vec<Box<dyn Any>>| {
-        match input.downcast_ref::<String>() {
-            Some(string) => Ok(string.is_empty() == false),
-            None => Err(Box::new(io::Error::new(io::ErrorKind::Other, "Input is not a string")) as Box<dyn Error>),
-            
-        }
-    }));
+impl FunctionCall {
+    pub fn new(function: SingleInputFunction, args: Vec<Value>) -> Self {
+        FunctionCall { function, args }
+    }
+}
 
-    m.insert(SingleInputFunction::Equal, Box::new(|input: Box<dyn Any>, args: Vec<Box<dyn Any>>| {
-        if args.len() != 1 {
-            return Err(Box::new(io::Error::new(io::ErrorKind::Other, "Equal: needs one argument")));
-        }
-        let input_str = input.downcast_ref::<String>().ok_or_else(|| {
-            Box::new(io::Error::new(io::ErrorKind::Other, "Input is not a string"))
-        })?;
-        let arg_str = args[0].downcast_ref::<String>().ok_or_else(|| {
-            Box::new(io::Error::new(io::ErrorKind::Other, "Argument is not a string"))
-        })?;
-        Ok(input_str == arg_str)
-    }));
-    
-    m
+/// How the default functions compare values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionOptions {
+    /// Numbers less than `epsilon` apart are equal, see `numeric::compare`.
+    #[serde(default = "default_epsilon")]
+    pub epsilon: f64,
+    #[serde(default)]
+    pub text: TextOptions,
+    #[serde(default)]
+    pub dates: DateOptions,
+    /// The time "now" is for `WithinLast` and `OlderThan`.
+    #[serde(skip, default = "system_clock")]
+    pub clock: Arc<dyn Clock>,
 }
 
-fn default_numeric_function(m: &mut HashMap<SingleInputFunction, Function>) -> &mut HashMap<SingleInputFunction, Function>{
+fn default_epsilon() -> f64 {
+    DEFAULT_EPSILON
+}
 
-    m.insert(SingleInputFunction::Greater, Box::new(|input: Box<dyn Any>, args: Vec<Box<dyn Any>>| {
-        let args_refs: Vec<&dyn Any> = args.iter().map(|arg| arg.as_ref()).collect();
+fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
 
-        match parse_numeric(SingleInputFunction::Greater, &*input, &args_refs, 1) {
-            Ok((num, args_no)) =>  Ok(num > args_no[0]),
-            Err(e) => Err(e)
+impl Default for FunctionOptions {
+    fn default() -> Self {
+        FunctionOptions {
+            epsilon: DEFAULT_EPSILON,
+            text: TextOptions::default(),
+            dates: DateOptions::default(),
+            clock: system_clock(),
         }
-    }));
+    }
+}
 
-    m.insert(SingleInputFunction::GreaterEq, Box::new(|input: Box<dyn Any>, args: Vec<Box<dyn Any>>| {
-        let args_refs: Vec<&dyn Any> = args.iter().map(|arg| arg.as_ref()).collect();
+/// All functions a condition can refer to, keyed by the names used in rule files.
+/// A function without a signature can be used, but its arguments are not validated and it is not listed.
+pub struct FunctionRegistry {
+    pub single: HashMap<SingleInputFunction, Function>,
+    pub list: HashMap<ListFunction, ListFunctionConstraint>,
+    pub aggregate: HashMap<Aggregator, AggregateFunction>,
+    pub signatures: Signatures,
+    /// The regular expressions of `Matches`, `NotMatches` and `MatchesAny`.
+    pub patterns: Patterns,
+    /// How the date functions read dates, so that the validator reads their arguments the same way.
+    pub dates: DateOptions,
+}
 
-        match parse_numeric(SingleInputFunction::GreaterEq, &*input, &args_refs, 1) {
-            Ok((num, args_no)) =>  Ok(num >= args_no[0]),
-            Err(e) => Err(e)
-            
+impl FunctionRegistry {
+    /// The default functions, comparing numbers and text as `options` say.
+    pub fn with_options(options: FunctionOptions) -> Self {
+        let patterns = Patterns::default();
+        let dates = options.dates.clone();
+        FunctionRegistry {
+            single: default_functions(options, &patterns),
+            list: default_list_function(),
+            aggregate: default_aggregate_function(),
+            signatures: default_signatures(),
+            patterns,
+            dates,
         }
-    }));
+    }
 
-    m.insert(SingleInputFunction::Lower, Box::new(|input: Box<dyn Any>, args: Vec<Box<dyn Any>>| {
-        let args_refs: Vec<&dyn Any> = args.iter().map(|arg| arg.as_ref()).collect();
+    /// The signatures of the registered functions, sorted by kind and name.
+    pub fn signatures(&self) -> Vec<&Signature> {
+        let single = self.single.keys().filter_map(|function| self.signatures.single.get(function));
+        let list = self.list.keys().filter_map(|function| self.signatures.list.get(function));
+        let aggregate = self.aggregate.keys().filter_map(|aggregator| self.signatures.aggregate.get(aggregator));
+        let mut signatures: Vec<&Signature> = single.chain(list).chain(aggregate).collect();
+        signatures.sort_by_key(|signature| (signature.kind, signature.name));
+        signatures
+    }
 
-        match parse_numeric(SingleInputFunction::Lower, &*input, &args_refs, 1) {
-            Ok((num, args_no)) =>  Ok(num < args_no[0]),
-            Err(e) => Err(e)
-            
-        }
-    }));
+    /// The signatures of the registered functions of one kind, sorted by name.
+    pub fn signatures_of(&self, kind: FunctionKind) -> Vec<&Signature> {
+        self.signatures().into_iter().filter(|signature| signature.kind == kind).collect()
+    }
+}
 
-    m.insert(SingleInputFunction::LowerEq, Box::new(|input: Box<dyn Any>, args: Vec<Box<dyn Any>>| {
-        let args_refs: Vec<&dyn Any> = args.iter().map(|arg| arg.as_ref()).collect();
+impl Default for FunctionRegistry {
+    fn default() -> Self {
+        FunctionRegistry::with_options(FunctionOptions::default())
+    }
+}
 
-        match parse_numeric(SingleInputFunction::LowerEq, &*input, &args_refs, 1) {
-            Ok((num, args_no)) =>  Ok(num <= args_no[0]),
-            Err(e) => Err(e)
-            
-        }
+fn default_functions(options: FunctionOptions, patterns: &Patterns) -> HashMap<SingleInputFunction, Function> {
+    let mut map: HashMap<SingleInputFunction, Function>  = HashMap::new();
+    default_general_functions(&mut map, options.epsilon, options.text);
+    default_numeric_functions(&mut map, options.epsilon);
+    default_text_functions(&mut map, options.text);
+    default_pattern_functions(&mut map, patterns);
+    default_date_functions(&mut map, &options.dates, &options.clock);
+    map
+}
+
+fn default_general_functions(m : &mut HashMap<SingleInputFunction, Function>, epsilon: f64, text: TextOptions) -> &mut HashMap<SingleInputFunction, Function> {
+   // let mut m = HashMap::new();
+    
+    m.insert(SingleInputFunction::Empty, Box::new(|input: &Value, _args: &[Value]| {
+        is_empty(SingleInputFunction::Empty, input)
+    })as Function);
+
+    m.insert(SingleInputFunction::NonEmpty, Box::new(|input: &Value, _args: &[Value]| {
+        Ok(!is_empty(SingleInputFunction::NonEmpty, input)?)
     }));
 
-    m.insert(SingleInputFunction::Between, Box::new(|input: Box<dyn Any>, args: Vec<Box<dyn Any>>| {
-        let args_refs: Vec<&dyn Any> = args.iter().map(|arg| arg.as_ref()).collect();
+    // A missing value never reaches a function, see `Signature::on_missing`.
+    m.insert(SingleInputFunction::Exists, Box::new(|_input, _args| Ok(true)));
+    m.insert(SingleInputFunction::IsNull, Box::new(|input, _args| Ok(input.is_null())));
+    m.insert(SingleInputFunction::IsNumber, Box::new(|input, _args| Ok(input.is_number())));
+    m.insert(SingleInputFunction::IsBoolean, Box::new(|input, _args| Ok(input.is_boolean())));
+    m.insert(SingleInputFunction::IsList, Box::new(|input, _args| Ok(input.is_array())));
 
-        match parse_numeric(SingleInputFunction::Between, &*input, &args_refs, 2) {
-            Ok((num, args_no)) =>  Ok(num > args_no[0] && num < args_no[1]),
-            Err(e) => Err(e)
-            
-        }
+    m.insert(SingleInputFunction::Equal, Box::new(move |input: &Value, args: &[Value]| {
+        check_args(SingleInputFunction::Equal, args, 1)?;
+        Ok(values_equal(input, &args[0], epsilon, text))
     }));
-    
-    m.insert(SingleInputFunction::BetweenEq, Box::new(|input: Box<dyn Any>, args: Vec<Box<dyn Any>>| {
-        let args_refs: Vec<&dyn Any> = args.iter().map(|arg| arg.as_ref()).collect();
 
-        match parse_numeric(SingleInputFunction::BetweenEq, &*input, &args_refs, 2) {
-            Ok((num, args_no)) =>  Ok(num >= args_no[0] && num <= args_no[0]),
-            Err(e) => Err(e)
-            
-        }
+    m.insert(SingleInputFunction::NotEqual, Box::new(move |input: &Value, args: &[Value]| {
+        check_args(SingleInputFunction::NotEqual, args, 1)?;
+        Ok(!values_equal(input, &args[0], epsilon, text))
     }));
 
-    m.insert(SingleInputFunction::NotBetween, Box::new(|input: Box<dyn Any>, args: Vec<Box<dyn Any>>| {
-        let args_refs: Vec<&dyn Any> = args.iter().map(|arg| arg.as_ref()).collect();
+    m.insert(SingleInputFunction::EqualAny, Box::new(move |input, args| {
+        Ok(args.iter().any(|arg| values_equal(input, arg, epsilon, text)))
+    }));
 
-        match parse_numeric(SingleInputFunction::Between, &*input, &args_refs, 2) {
-            Ok((num, args_no)) =>  Ok(num <= args_no[0] || num >= args_no[0]),
-            Err(e) => Err(e)
-            
-        }
+    m.insert(SingleInputFunction::NotEqualAny, Box::new(move |input, args| {
+        Ok(!args.iter().any(|arg| values_equal(input, arg, epsilon, text)))
     }));
 
-    m.insert(SingleInputFunction::NotBetweenEq, Box::new(|input: Box<dyn Any>, args: Vec<Box<dyn Any>>| {
-        let args_refs: Vec<&dyn Any> = args.iter().map(|arg| arg.as_ref()).collect();
+    m
+}
 
-        match parse_numeric(SingleInputFunction::NotBetweenEq, &*input, &args_refs, 2) {
-            Ok((num, args_no)) =>  Ok(num > args_no[0] && num < args_no[0]),
-            Err(e) => Err(e)
-            
+// `Equal`: a number and a string by value when the string holds a number, as the comparisons read them; two strings
+// in the normal form of the text options; other values by `numbers_equal`.
+fn values_equal(a: &Value, b: &Value, epsilon: f64, text: TextOptions) -> bool {
+    match (a, b) {
+        (Value::Number(_), Value::String(_)) | (Value::String(_), Value::Number(_)) => {
+            match (Number::from_value(a), Number::from_value(b)) {
+                (Some(a), Some(b)) => compare(a, b, epsilon) == Ordering::Equal,
+                _ => false,
+            }
         }
-    }));
+        (Value::String(a), Value::String(b)) => text.normalize(a) == text.normalize(b),
+        _ => numbers_equal(a, b, epsilon),
+    }
+}
 
+// Whether a string, list or object has no characters, elements or keys.
+fn is_empty(function_name: SingleInputFunction, input: &Value) -> Result<bool> {
+    match input {
+        Value::String(string) => Ok(string.is_empty()),
+        Value::Array(elements) => Ok(elements.is_empty()),
+        Value::Object(object) => Ok(object.is_empty()),
+        _ => Err(LogicLoomError::type_mismatch(function_name, Subject::Input, "string, list or object", input)),
+    }
+}
 
-    m
+fn check_args(function_name: SingleInputFunction, args: &[Value], required_args_count: usize) -> Result<()> {
+    if args.len() != required_args_count {
+        return Err(LogicLoomError::argument_count(function_name, required_args_count, args.len()));
+    }
+    Ok(())
+}
 
+fn input_str(function_name: SingleInputFunction, input: &Value) -> Result<&str> {
+    input.as_str().ok_or_else(|| LogicLoomError::type_mismatch(function_name, Subject::Input, "string", input))
 }
 
-fn parse_numeric( function_name: SingleInputFunction ,
-    input: &dyn Any,
-    args: &[&dyn Any],
-    required_args_count: usize) -> Result<(f64, Vec<f64>), Box<dyn Error>> {
-        let input_ref = input.downcast_ref::<String>().ok_or_else(|| {
-            format!(
-                "[{:?}]: could not convert input [{:?}] to number: input is not a string",
-                function_name, input
-            )
-        })?;
-        let mut input_no = input_ref.parse::<f64>().map_err(|e| {
-            format!("Failed to parse string as f64: {}", e)
-
-        })?;
-        match input_ref.parse::<f64>() {
-            Ok(number) =>  { input_no = number; },
-            Err(e) => println!("Failed to parse string as f64: {}", e)
+fn arg_str(function_name: SingleInputFunction, args: &[Value], i: usize) -> Result<&str> {
+    args[i].as_str().ok_or_else(|| LogicLoomError::type_mismatch(function_name, Subject::Argument(i), "string", &args[i]))
+}
 
-        }
+/// Reads a JSON number, or a string holding one, as `f64`.
+pub fn to_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(string) => string.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+}
 
-        if args.len() != required_args_count {
-            return Err(format!(
-                "[{:?}]: not enough arguments provided, expected {} but got {}",
-                function_name,
-                required_args_count,
-                args.len()
-            )
-            .into());
-        }
-        let mut args_nr = Vec::with_capacity(required_args_count);
-        for (i, &arg) in args.iter().enumerate() {
-            let arg_ref = arg.downcast_ref::<String>().ok_or_else(|| {
-                format!(
-                    "[{:?}]: could not convert argument [{}] [{:?}] to number: argument is not a string",
-                    function_name, i, arg
-                )
-            })?;
-             match arg_ref.parse::<f64>() {
-                 Ok(num) =>  {  args_nr.push(num); }
-                 Err(err) =>   { return Err(format!(
-                    "[{:?}]: could not convert argument [{}] [{:?}] to number: {}",
-                    function_name, i, arg_ref, err
-                ).into()); }
-
-            };
-        }
-        Ok((input_no, args_nr))
+/// Like `to_number`, but tells a value of the wrong type apart from a string that is not a number.
+pub fn parse_number(function_name: impl std::fmt::Debug + Copy, subject: Subject, value: &Value) -> Result<f64> {
+    match value {
+        Value::Number(_) | Value::String(_) => to_number(value).ok_or_else(|| {
+            LogicLoomError::parse(function_name, format!("could not convert {} [{}] to number", subject, value))
+        }),
+        _ => Err(LogicLoomError::type_mismatch(function_name, subject, "number", value)),
+    }
 }
 
+#[cfg(test)]
+mod tests {
 
+    use super::*;
+    use super::text::Normalization;
+    use serde_json::json;
 
-fn default_string_function(m: &mut HashMap<SingleInputFunction, Function>)  -> &mut HashMap<SingleInputFunction, Function> {
-    m.insert(SingleInputFunction::EqualIgnoreCase, Box::new(|input, args| {
-        if args.len() != 1 {
-            return Err("EqualIgnoreCase: needs one argument".into());
-        }
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        let arg_str = args[0].downcast_ref::<String>().ok_or("Argument is not a string")?;
-        Ok(input_str.eq_ignore_ascii_case(arg_str))
-    }));
+    #[test]
+    fn test_default_single_input_functions() {
+        let default_functions = FunctionRegistry::default().single;
 
-    m.insert(SingleInputFunction::EqualAnyIgnoreCase, Box::new(|input, args| {
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        for arg in args {
-            if let Some(arg_str) = arg.downcast_ref::<String>() {
-                if input_str.eq_ignore_ascii_case(arg_str) {
-                    return Ok(true);
-                }
-            }
-        }
-        Ok(false)
-    }));
+        let result = default_functions[&SingleInputFunction::Empty](&Value::from(""), &[]);
+        assert!(result.is_ok_and(|x| x));
 
-    m.insert(SingleInputFunction::EqualAny, Box::new(|input, args| {
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        for arg in args {
-            if let Some(arg_str) = arg.downcast_ref::<String>() {
-                if input_str == arg_str {
-                    return Ok(true);
-                }
-            }
-        }
-        Ok(false)
-    }));
+        let result = default_functions[&SingleInputFunction::NonEmpty](&Value::from("ankit"), &[]);
+        assert!(result.is_ok_and(|x| x));
 
-    m.insert(SingleInputFunction::NotEqualAny, Box::new(|input, args| {
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        for arg in args {
-            if let Some(arg_str) = arg.downcast_ref::<String>() {
-                if input_str == arg_str {
-                    return Ok(false);
-                }
-            }
-        }
-        Ok(true)
-    }));
+        let result = default_functions[&SingleInputFunction::Equal](&Value::from("ankit"), &[Value::from("ankit")]);
+        assert!(result.is_ok_and(|x| x));
 
-    m.insert(SingleInputFunction::StartsWith, Box::new(|input, args| {
-        if args.len() != 1 {
-            return Err("StartsWith: needs one argument".into());
-        }
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        let arg_str = args[0].downcast_ref::<String>().ok_or("Argument is not a string")?;
-        Ok(input_str.starts_with(arg_str))
-    }));
+        let result = default_functions[&SingleInputFunction::Equal](&Value::from("ankit"), &[Value::from("anki")]);
+        assert!(result.is_ok_and(|x| !x));
+    
+        let result = default_functions[&SingleInputFunction::Greater](&Value::from("1"), &[Value::from("0")]);
+        assert!(result.is_ok_and(|x| x));
 
-    m.insert(SingleInputFunction::StartsWithIgnoreCase, Box::new(|input, args| {
-        if args.len() != 1 {
-            return Err("StartsWith: needs one argument".into());
-        }
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        let arg_str = args[0].downcast_ref::<String>().ok_or("Argument is not a string")?;
-        Ok(input_str.to_lowercase().starts_with(&arg_str.to_lowercase()))
-    }));
+        let result = default_functions[&SingleInputFunction::GreaterEq](&Value::from("1"), &[Value::from("1")]);
+        assert!(result.is_ok_and(|x| x));
 
-    m.insert(SingleInputFunction::EndsWith, Box::new(|input, args| {
-        if args.len() != 1 {
-            return Err("EndsWith: needs one argument".into());
-        }
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        let arg_str = args[0].downcast_ref::<String>().ok_or("Argument is not a string")?;
-        Ok(input_str.ends_with(arg_str))
-    }));
+        let result = default_functions[&SingleInputFunction::Lower](&Value::from("0"), &[Value::from("1")]);
+        assert!(result.is_ok_and(|x| x));
 
-    m.insert(SingleInputFunction::EndsWithIgnoreCase, Box::new(|input, args| {
-        if args.len() != 1 {
-            return Err("EndsWithIgnoreCase: needs one argument".into());
-        }
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        let arg_str = args[0].downcast_ref::<String>().ok_or("Argument is not a string")?;
-        Ok(input_str.to_lowercase().ends_with(&arg_str.to_lowercase()))
-    }));
 
-    m.insert(SingleInputFunction::Contains, Box::new(|input, args| {
-        if args.len() != 1 {
-            return Err("EndsWithIgnoreCase: needs one argument".into());
-        }
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        let arg_str = args[0].downcast_ref::<String>().ok_or("Argument is not a string")?;
-        Ok(input_str.contains(arg_str))
-    }));
-    m.insert(SingleInputFunction::ContainsIgnoreCase, Box::new(|input, args| {
-        if args.len() != 1 {
-            return Err("EndsWithIgnoreCase: needs one argument".into());
-        }
-        let input_str = input.downcast_ref::<String>().ok_or("Input is not a string")?;
-        let arg_str = args[0].downcast_ref::<String>().ok_or("Argument is not a string")?;
-        Ok(input_str.to_lowercase().contains(&arg_str.to_lowercase()))
-    }));
-    
-    m
+        let result = default_functions[&SingleInputFunction::Lower](&Value::from("0"), &[Value::from("1")]);
+        assert!(result.is_ok_and(|x| x));
 
-}
+        let result = default_functions[&SingleInputFunction::LowerEq](&Value::from("0"), &[Value::from("1")]);
+        assert!(result.is_ok_and(|x| x));
 
-#[cfg(test)]
-mod tests {
-    use std::result;
+        let result = default_functions[&SingleInputFunction::Between](&Value::from("2"), &[Value::from("1"), Value::from("3")]);
+        assert!(result.is_ok_and(|x| x));
 
-    use super::*;
+        let result = default_functions[&SingleInputFunction::BetweenEq](&Value::from("1"), &[Value::from("1"), Value::from("3")]);
+        assert!(result.is_ok_and(|x| x));
 
-    #[test]
-    fn test_default_single_input_functions() {
-        let default_functions = default();
+        let result = default_functions[&SingleInputFunction::NotBetween](&Value::from("2"), &[Value::from("1"), Value::from("3")]);
+        assert!(result.is_ok_and(|x| !x));
 
-        let result = default_functions[&SingleInputFunction::Empty](Box::new(String::from("")), vec![]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::NotBetweenEq](&Value::from("1"), &[Value::from("1"), Value::from("3")]);
+        assert!(result.is_ok_and(|x| !x));
 
-        let result = default_functions[&SingleInputFunction::NonEmpty](Box::new(String::from("ankit")), vec![]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::EqualIgnoreCase](&Value::from("Ankit"), &[Value::from("ANKIT")]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::Equal](Box::new(String::from("ankit")), vec![Box::new(String::from("ankit"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::EqualAnyIgnoreCase](&Value::from("Ankit"), &[Value::from("SHEORAN"), Value::from("ANKIT")]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::Equal](Box::new(String::from("ankit")), vec![Box::new(String::from("anki"))]);
-        assert!(result.is_ok_and(|x| x == false));
-    
-        let result = default_functions[&SingleInputFunction::Greater](Box::new(String::from("1")), vec![Box::new(String::from("0"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::EqualAny](&Value::from("Ankit"), &[Value::from("SHEORAN"), Value::from("Ankit")]);
+        assert!(result.is_ok_and(|x| x));
+
+        let result = default_functions[&SingleInputFunction::NotEqualAny](&Value::from("Ankit"), &[Value::from("SHEORAN"), Value::from("ANKIT")]);
+        assert!(result.is_ok_and(|x| x));
+
+        let result = default_functions[&SingleInputFunction::StartsWith](&Value::from("AnkitSheoran"), &[Value::from("Ankit")]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::GreaterEq](Box::new(String::from("1")), vec![Box::new(String::from("1"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::StartsWithIgnoreCase](&Value::from("AnkitSheoran"), &[Value::from("ANKIT")]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::Lower](Box::new(String::from("0")), vec![Box::new(String::from("1"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::EndsWith](&Value::from("AnkitSheoran"), &[Value::from("Sheoran")]);
+        assert!(result.is_ok_and(|x| x));
 
+        let result = default_functions[&SingleInputFunction::EndsWithIgnoreCase](&Value::from("AnkitSheoran"), &[Value::from("SHEORAN")]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::Lower](Box::new(String::from("0")), vec![Box::new(String::from("1"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::Contains](&Value::from("AnkitSheoran"), &[Value::from("Sheoran")]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::LowerEq](Box::new(String::from("0")), vec![Box::new(String::from("1"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::ContainsIgnoreCase](&Value::from("AnkitSheoran"), &[Value::from("SHEORAN")]);
+        assert!(result.is_ok_and(|x| x));
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let default_functions = FunctionRegistry::default().single;
+
+        let result = default_functions[&SingleInputFunction::Greater](&json!(21), &[json!(18)]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::Between](Box::new(String::from("2")), vec![Box::new(String::from("1")), Box::new(String::from("3"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::BetweenEq](&json!(2.5), &[json!(1), json!("3")]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::BetweenEq](Box::new(String::from("1")), vec![Box::new(String::from("1")), Box::new(String::from("3"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::Equal](&json!(1), &[json!(1.0)]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::NotBetween](Box::new(String::from("2")), vec![Box::new(String::from("1")), Box::new(String::from("3"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::Equal](&json!(true), &[json!(true)]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::NotBetweenEq](Box::new(String::from("1")), vec![Box::new(String::from("1")), Box::new(String::from("3"))]);
-        assert!(result.is_ok_and(|x| x == false));
+        let result = default_functions[&SingleInputFunction::Equal](&json!(null), &[json!("null")]);
+        assert!(result.is_ok_and(|x| !x));
 
-        let result = default_functions[&SingleInputFunction::EqualIgnoreCase](Box::new(String::from("Ankit")), vec![Box::new(String::from("ANKIT"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::EqualAny](&json!(3), &[json!(1), json!(2), json!(3)]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::EqualAnyIgnoreCase](Box::new(String::from("Ankit")), vec![Box::new(String::from("SHEORAN")), Box::new(String::from("ANKIT"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::EqualAny](&json!(0.1 + 0.2), &[json!(0.1), json!(0.3)]);
+        assert!(result.is_ok_and(|x| x));
 
-        let result = default_functions[&SingleInputFunction::EqualAny](Box::new(String::from("Ankit")), vec![Box::new(String::from("SHEORAN")), Box::new(String::from("Ankit"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::NotEqualAny](&json!(0.1 + 0.2), &[json!(0.3)]);
+        assert!(result.is_ok_and(|x| !x));
 
-        let result = default_functions[&SingleInputFunction::NotEqualAny](Box::new(String::from("Ankit")), vec![Box::new(String::from("SHEORAN")), Box::new(String::from("ANKIT"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::Greater](&json!(true), &[json!(1)]);
+        assert!(result.is_err());
 
-        let result = default_functions[&SingleInputFunction::StartsWith](Box::new(String::from("AnkitSheoran")), vec![Box::new(String::from("Ankit"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let result = default_functions[&SingleInputFunction::StartsWith](&json!(["a"]), &[json!("a")]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_numeric_strings() {
+        let default_functions = FunctionRegistry::default().single;
+        let holds = |function: SingleInputFunction, input: Value, args: &[Value]| default_functions[&function](&input, args).unwrap();
+
+        assert!(holds(SingleInputFunction::Equal, json!("42"), &[json!(42)]));
+        assert!(holds(SingleInputFunction::Equal, json!(42), &[json!(" 42.0 ")]));
+        assert!(holds(SingleInputFunction::GreaterEq, json!("42"), &[json!(42)]));
+        assert!(holds(SingleInputFunction::LowerEq, json!(42), &[json!("42.0")]));
+        assert!(holds(SingleInputFunction::BetweenEq, json!("42"), &[json!(42), json!("42")]));
+        assert!(holds(SingleInputFunction::EqualAny, json!("0.3"), &[json!(0.1 + 0.2)]));
+        assert!(!holds(SingleInputFunction::NotEqual, json!("42"), &[json!(42)]));
+        assert!(holds(SingleInputFunction::NotEqualAny, json!("abc"), &[json!(42)]));
+        // Two strings are compared as text.
+        assert!(!holds(SingleInputFunction::Equal, json!("42"), &[json!("42.0")]));
+    }
 
-        let result = default_functions[&SingleInputFunction::StartsWithIgnoreCase](Box::new(String::from("AnkitSheoran")), vec![Box::new(String::from("ANKIT"))]);
-        assert!(result.is_ok_and(|x| x == true));
+    #[test]
+    fn test_equal_normalizes_text() {
+        let default_functions = FunctionRegistry::default().single;
+        let result = default_functions[&SingleInputFunction::Equal](&json!("Café"), &[json!("Cafe\u{301}")]);
+        assert!(result.is_ok_and(|x| !x));
+
+        let text = TextOptions { normalization: Normalization::Nfc, ..TextOptions::default() };
+        let functions = FunctionRegistry::with_options(FunctionOptions { text, ..FunctionOptions::default() }).single;
+        let result = functions[&SingleInputFunction::Equal](&json!("Café"), &[json!("Cafe\u{301}")]);
+        assert!(result.is_ok_and(|x| x));
+        let result = functions[&SingleInputFunction::NotEqualAny](&json!("Café"), &[json!("Cafe"), json!("Cafe\u{301}")]);
+        assert!(result.is_ok_and(|x| !x));
+
+        let text = TextOptions { ignore_accents: true, ..TextOptions::default() };
+        let functions = FunctionRegistry::with_options(FunctionOptions { text, ..FunctionOptions::default() }).single;
+        let result = functions[&SingleInputFunction::EqualAny](&json!("Müller"), &[json!("Meier"), json!("Muller")]);
+        assert!(result.is_ok_and(|x| x));
+        let result = functions[&SingleInputFunction::NotEqual](&json!("Müller"), &[json!("muller")]);
+        assert!(result.is_ok_and(|x| x));
+    }
+
+    #[test]
+    fn test_type_predicates() {
+        let default_functions = FunctionRegistry::default().single;
+        let holds = |function: SingleInputFunction, input: Value| default_functions[&function](&input, &[]).unwrap();
+
+        assert!(holds(SingleInputFunction::Exists, json!(null)));
+        assert!(holds(SingleInputFunction::IsNull, json!(null)));
+        assert!(!holds(SingleInputFunction::IsNull, json!("")));
+        assert!(holds(SingleInputFunction::IsNumber, json!(1.5)));
+        assert!(!holds(SingleInputFunction::IsNumber, json!("1.5")));
+        assert!(holds(SingleInputFunction::IsBoolean, json!(false)));
+        assert!(!holds(SingleInputFunction::IsBoolean, json!("true")));
+        assert!(holds(SingleInputFunction::IsList, json!([])));
+        assert!(!holds(SingleInputFunction::IsList, json!({})));
+
+        assert!(holds(SingleInputFunction::Empty, json!([])));
+        assert!(holds(SingleInputFunction::Empty, json!({})));
+        assert!(holds(SingleInputFunction::NonEmpty, json!([null])));
+        assert!(holds(SingleInputFunction::NonEmpty, json!({"a": 1})));
+        let err = default_functions[&SingleInputFunction::Empty](&json!(0), &[]).unwrap_err();
+        assert_eq!(err.to_string(), "[Empty]: input is not a string, list or object, found number");
+    }
+
+    #[test]
+    fn test_function_errors() {
+        let default_functions = FunctionRegistry::default().single;
 
-        let result = default_functions[&SingleInputFunction::EndsWith](Box::new(String::from("AnkitSheoran")), vec![Box::new(String::from("Sheoran"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let err = default_functions[&SingleInputFunction::Contains](&json!("abc"), &[]).unwrap_err();
+        assert!(matches!(err, LogicLoomError::ArgumentCount { expected: 1, found: 0, .. }));
+        assert_eq!(err.to_string(), "[Contains]: expected 1 argument but got 0");
 
-        let result = default_functions[&SingleInputFunction::EndsWithIgnoreCase](Box::new(String::from("AnkitSheoran")), vec![Box::new(String::from("SHEORAN"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let err = default_functions[&SingleInputFunction::StartsWithIgnoreCase](&json!("abc"), &[json!(1)]).unwrap_err();
+        assert_eq!(err.to_string(), "[StartsWithIgnoreCase]: argument [0] is not a string, found number");
 
-        let result = default_functions[&SingleInputFunction::Contains](Box::new(String::from("AnkitSheoran")), vec![Box::new(String::from("Sheoran"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let err = default_functions[&SingleInputFunction::Greater](&json!("abc"), &[json!(1)]).unwrap_err();
+        assert_eq!(err.code(), "parse");
 
-        let result = default_functions[&SingleInputFunction::ContainsIgnoreCase](Box::new(String::from("AnkitSheoran")), vec![Box::new(String::from("SHEORAN"))]);
-        assert!(result.is_ok_and(|x| x == true));
+        let err = default_functions[&SingleInputFunction::Greater](&json!(null), &[json!(1)]).unwrap_err();
+        assert_eq!(err.to_string(), "[Greater]: input is not a number, found null");
     }
 }
\ No newline at end of file
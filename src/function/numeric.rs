@@ -0,0 +1,247 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use super::{check_args, Function, SingleInputFunction};
+use crate::error::{LogicLoomError, Result, Subject};
+
+/// The default tolerance of numeric comparisons, see `compare`.
+pub const DEFAULT_EPSILON: f64 = 1e-9;
+
+/// A number read from an input or argument. Integers are kept apart from floats so that large integers are compared
+/// exactly instead of after rounding to `f64`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Integer(i128),
+    Float(f64),
+}
+
+impl Number {
+    /// Reads a JSON number, or a string holding one. Strings holding `NaN` or infinity are not numbers.
+    pub fn from_value(value: &Value) -> Option<Number> {
+        match value {
+            Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+                (Some(i), _) => Some(Number::Integer(i as i128)),
+                (_, Some(u)) => Some(Number::Integer(u as i128)),
+                _ => number.as_f64().map(Number::Float),
+            },
+            Value::String(string) => {
+                let string = string.trim();
+                match string.parse::<i128>() {
+                    Ok(i) => Some(Number::Integer(i)),
+                    Err(_) => string.parse::<f64>().ok().filter(|f| f.is_finite()).map(Number::Float),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Number::Integer(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+/// Compares two numbers. Two integers are compared exactly; otherwise numbers less than `epsilon` apart are equal,
+/// so `0.1 + 0.2` equals `0.3` and `1` equals `1.0`.
+pub fn compare(a: Number, b: Number, epsilon: f64) -> Ordering {
+    match (a, b) {
+        (Number::Integer(a), Number::Integer(b)) => a.cmp(&b),
+        _ => {
+            let (a, b) = (a.as_f64(), b.as_f64());
+            if (a - b).abs() <= epsilon {
+                Ordering::Equal
+            } else if a < b {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        }
+    }
+}
+
+/// Like `Number::from_value`, but tells a value of the wrong type apart from a string that is not a number.
+fn parse(function: SingleInputFunction, subject: Subject, value: &Value) -> Result<Number> {
+    match value {
+        Value::Number(_) | Value::String(_) => Number::from_value(value).ok_or_else(|| {
+            LogicLoomError::parse(function, format!("could not convert {} [{}] to number", subject, value))
+        }),
+        _ => Err(LogicLoomError::type_mismatch(function, subject, "number", value)),
+    }
+}
+
+// How the input compares to each argument; `None` for a `null` bound of a range, which is open on that side.
+type Orderings = [Option<Ordering>];
+
+fn insert(
+    m: &mut HashMap<SingleInputFunction, Function>,
+    function: SingleInputFunction,
+    epsilon: f64,
+    test: fn(&Orderings) -> bool,
+) {
+    let (args_count, open_bounds) = match function {
+        SingleInputFunction::Between
+        | SingleInputFunction::BetweenEq
+        | SingleInputFunction::NotBetween
+        | SingleInputFunction::NotBetweenEq => (2, true),
+        _ => (1, false),
+    };
+    m.insert(function, Box::new(move |input: &Value, args: &[Value]| {
+        let input = parse(function, Subject::Input, input)?;
+        check_args(function, args, args_count)?;
+        let orderings = args.iter().enumerate()
+            .map(|(i, arg)| match arg {
+                Value::Null if open_bounds => Ok(None),
+                _ => parse(function, Subject::Argument(i), arg).map(|arg| Some(compare(input, arg, epsilon))),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(test(&orderings))
+    }));
+}
+
+// The input lies above the lower bound and below the upper bound, or on them when `inclusive`.
+fn within(orderings: &Orderings, inclusive: bool) -> bool {
+    let above = match orderings[0] {
+        None => true,
+        Some(ordering) => ordering == Ordering::Greater || (inclusive && ordering == Ordering::Equal),
+    };
+    let below = match orderings[1] {
+        None => true,
+        Some(ordering) => ordering == Ordering::Less || (inclusive && ordering == Ordering::Equal),
+    };
+    above && below
+}
+
+/// The comparisons and ranges, with numbers compared by `compare`. A range bound may be `null` for a range that is
+/// open on that side, e.g. `Between [null, 100]` is below 100.
+pub fn default_numeric_functions(m: &mut HashMap<SingleInputFunction, Function>, epsilon: f64) {
+    insert(m, SingleInputFunction::Greater, epsilon, |o| o[0] == Some(Ordering::Greater));
+    insert(m, SingleInputFunction::GreaterEq, epsilon, |o| o[0] != Some(Ordering::Less));
+    insert(m, SingleInputFunction::Lower, epsilon, |o| o[0] == Some(Ordering::Less));
+    insert(m, SingleInputFunction::LowerEq, epsilon, |o| o[0] != Some(Ordering::Greater));
+    insert(m, SingleInputFunction::Between, epsilon, |o| within(o, false));
+    insert(m, SingleInputFunction::BetweenEq, epsilon, |o| within(o, true));
+    insert(m, SingleInputFunction::NotBetween, epsilon, |o| !within(o, false));
+    insert(m, SingleInputFunction::NotBetweenEq, epsilon, |o| !within(o, true));
+}
+
+/// `Equal` for numbers: by value within `epsilon`. Other values, including strings holding numbers, structurally.
+pub fn numbers_equal(a: &Value, b: &Value, epsilon: f64) -> bool {
+    match (a, b) {
+        (Value::Number(_), Value::Number(_)) => match (Number::from_value(a), Number::from_value(b)) {
+            (Some(a), Some(b)) => compare(a, b, epsilon) == Ordering::Equal,
+            _ => a == b,
+        },
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn functions(epsilon: f64) -> HashMap<SingleInputFunction, Function> {
+        let mut m = HashMap::new();
+        default_numeric_functions(&mut m, epsilon);
+        m
+    }
+
+    fn check(function: SingleInputFunction, args: Value, cases: &[(Value, bool)]) {
+        let functions = functions(DEFAULT_EPSILON);
+        let args = args.as_array().unwrap();
+        for (input, expected) in cases {
+            assert_eq!(functions[&function](input, args).unwrap(), *expected, "{:?} {} {:?}", function, input, args);
+        }
+    }
+
+    #[test]
+    fn comparisons() {
+        use SingleInputFunction::*;
+        check(Greater, json!([10]), &[(json!(9), false), (json!(10), false), (json!(10.0), false), (json!(10.5), true), (json!("11"), true)]);
+        check(GreaterEq, json!([10]), &[(json!(9.99), false), (json!(10), true), (json!("10.0"), true), (json!(11), true)]);
+        check(Lower, json!([10]), &[(json!(9), true), (json!(10), false), (json!(10.0), false), (json!(11), false), (json!(-1e300), true)]);
+        check(LowerEq, json!(["10"]), &[(json!(9), true), (json!(10), true), (json!(10.000001), false), (json!(11), false)]);
+    }
+
+    #[test]
+    fn ranges() {
+        use SingleInputFunction::*;
+        let inputs = [json!(0), json!(1), json!(2), json!(3), json!(4)];
+        let table = [
+            (Between, [false, false, true, false, false]),
+            (BetweenEq, [false, true, true, true, false]),
+            (NotBetween, [true, true, false, true, true]),
+            (NotBetweenEq, [true, false, false, false, true]),
+        ];
+        for (function, expected) in table {
+            let cases: Vec<(Value, bool)> = inputs.iter().cloned().zip(expected).collect();
+            check(function, json!([1, 3]), &cases);
+            check(function, json!([1.0, "3"]), &cases);
+        }
+
+        // Bounds in the wrong order make an empty range.
+        check(BetweenEq, json!([3, 1]), &[(json!(2), false), (json!(1), false)]);
+        check(NotBetweenEq, json!([3, 1]), &[(json!(2), true)]);
+    }
+
+    #[test]
+    fn open_ranges() {
+        use SingleInputFunction::*;
+        check(Between, json!([null, 100]), &[(json!(-1e9), true), (json!(99.5), true), (json!(100), false)]);
+        check(BetweenEq, json!([null, 100]), &[(json!(100), true), (json!(100.5), false)]);
+        check(Between, json!([0, null]), &[(json!(0), false), (json!(1e12), true)]);
+        check(BetweenEq, json!([0, null]), &[(json!(0), true), (json!(-0.5), false)]);
+        check(NotBetween, json!([0, null]), &[(json!(0), true), (json!(1), false)]);
+        check(NotBetweenEq, json!([null, 100]), &[(json!(100), false), (json!(101), true)]);
+        check(BetweenEq, json!([null, null]), &[(json!(42), true)]);
+    }
+
+    #[test]
+    fn integers_and_floats() {
+        use SingleInputFunction::*;
+        // 2^53 + 1 is not an `f64`, integers are compared exactly.
+        check(Greater, json!([9007199254740992_u64]), &[(json!(9007199254740993_u64), true)]);
+        check(BetweenEq, json!([u64::MAX, u64::MAX]), &[(json!(u64::MAX), true), (json!(u64::MAX - 1), false)]);
+        check(Greater, json!(["-9007199254740993"]), &[(json!(-9007199254740992_i64), true)]);
+        check(LowerEq, json!([0.3]), &[(json!(0.1 + 0.2), true)]);
+        check(Lower, json!([0.3]), &[(json!(0.1 + 0.2), false)]);
+        check(BetweenEq, json!([0.3, 1]), &[(json!(0.1 + 0.2), true)]);
+        check(Between, json!([0.3, 1]), &[(json!(0.1 + 0.2), false)]);
+    }
+
+    #[test]
+    fn configurable_epsilon() {
+        let exact = functions(0.0);
+        let loose = functions(0.01);
+
+        assert!(exact[&SingleInputFunction::Greater](&json!(100.001), &[json!(100)]).unwrap());
+        assert!(!loose[&SingleInputFunction::Greater](&json!(100.001), &[json!(100)]).unwrap());
+        assert!(!exact[&SingleInputFunction::LowerEq](&json!(0.1 + 0.2), &[json!(0.3)]).unwrap());
+        assert!(loose[&SingleInputFunction::BetweenEq](&json!(99.995), &[json!(0), json!(99.99)]).unwrap());
+        // Integers are exact whatever the epsilon.
+        assert!(loose[&SingleInputFunction::Greater](&json!(101), &[json!(100)]).unwrap());
+
+        assert!(numbers_equal(&json!(1), &json!(1.0), 0.0));
+        assert!(numbers_equal(&json!(0.1 + 0.2), &json!(0.3), DEFAULT_EPSILON));
+        assert!(!numbers_equal(&json!(0.1 + 0.2), &json!(0.3), 0.0));
+        assert!(!numbers_equal(&json!("1"), &json!(1), DEFAULT_EPSILON));
+    }
+
+    #[test]
+    fn errors_name_the_function() {
+        let functions = functions(DEFAULT_EPSILON);
+        let error = |function: SingleInputFunction, input: Value, args: Value| {
+            functions[&function](&input, args.as_array().unwrap()).unwrap_err().to_string()
+        };
+
+        assert_eq!(error(SingleInputFunction::NotBetween, json!(true), json!([1, 2])), "[NotBetween]: input is not a number, found boolean");
+        assert_eq!(error(SingleInputFunction::NotBetweenEq, json!(1), json!([1])), "[NotBetweenEq]: expected 2 arguments but got 1");
+        assert_eq!(error(SingleInputFunction::Greater, json!(1), json!([null])), "[Greater]: argument [0] is not a number, found null");
+        assert_eq!(error(SingleInputFunction::Lower, json!("NaN"), json!([1])), "[Lower]: could not convert input [\"NaN\"] to number");
+        assert_eq!(error(SingleInputFunction::Between, json!(1), json!(["x", 2])), "[Between]: could not convert argument [0] [\"x\"] to number");
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::{arg_str, check_args, input_str, Function, SingleInputFunction};
+use crate::error::{LogicLoomError, Result};
+
+/// The groups captured by the regular expressions of a rule, by number (`"0"` for the whole match) and by name.
+/// A group that did not take part in the match is `null`.
+pub type Captures = HashMap<String, Value>;
+
+/// How many compiled patterns `Patterns::default` keeps.
+pub const DEFAULT_PATTERN_CAPACITY: usize = 1024;
+
+/// Compiled regular expressions, shared by the functions of a `FunctionRegistry`. Patterns are compiled when rules
+/// are validated or added to an engine, so evaluating a rule only looks them up. At most `capacity` patterns are
+/// kept: the one used least recently makes room for a new one, and is compiled again when it is used again.
+#[derive(Debug, Clone)]
+pub struct Patterns {
+    compiled: Arc<RwLock<HashMap<String, Compiled>>>,
+    capacity: usize,
+    // Counts the lookups, telling which pattern was used least recently.
+    lookups: Arc<AtomicU64>,
+}
+
+#[derive(Debug)]
+struct Compiled {
+    regex: Arc<Regex>,
+    used: AtomicU64,
+}
+
+impl Default for Patterns {
+    fn default() -> Self {
+        Patterns::with_capacity(DEFAULT_PATTERN_CAPACITY)
+    }
+}
+
+impl Patterns {
+    /// Keeps at most `capacity` compiled patterns, at least one.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Patterns { compiled: Arc::default(), capacity: capacity.max(1), lookups: Arc::default() }
+    }
+
+    /// The compiled pattern, compiling it on first use.
+    pub fn get(&self, pattern: &str) -> std::result::Result<Arc<Regex>, regex::Error> {
+        let lookup = self.lookups.fetch_add(1, Ordering::Relaxed);
+        if let Some(compiled) = self.compiled.read().unwrap_or_else(PoisonError::into_inner).get(pattern) {
+            compiled.used.store(lookup, Ordering::Relaxed);
+            return Ok(compiled.regex.clone());
+        }
+        let regex = Arc::new(Regex::new(pattern)?);
+        let mut compiled = self.compiled.write().unwrap_or_else(PoisonError::into_inner);
+        if compiled.len() >= self.capacity && !compiled.contains_key(pattern) {
+            let least_recent = compiled.iter().min_by_key(|(_, compiled)| compiled.used.load(Ordering::Relaxed));
+            if let Some(least_recent) = least_recent.map(|(pattern, _)| pattern.clone()) {
+                compiled.remove(&least_recent);
+            }
+        }
+        let compiled = compiled.entry(pattern.to_owned()).or_insert(Compiled { regex, used: AtomicU64::new(lookup) });
+        Ok(compiled.regex.clone())
+    }
+
+    /// The number of compiled patterns.
+    pub fn len(&self) -> usize {
+        self.compiled.read().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn compiled(&self, function: SingleInputFunction, pattern: &str) -> Result<Arc<Regex>> {
+        self.get(pattern).map_err(|e| LogicLoomError::parse(function, invalid_pattern(pattern, &e)))
+    }
+
+    /// The groups captured by the first pattern of a `Matches` or `MatchesAny` call that matches `input`, `None` when
+    /// no pattern matches.
+    pub fn captures(&self, function: SingleInputFunction, input: &Value, args: &[Value]) -> Result<Option<Captures>> {
+        if function == SingleInputFunction::Matches {
+            check_args(function, args, 1)?;
+        }
+        let input = input_str(function, input)?;
+        for i in 0..args.len() {
+            let regex = self.compiled(function, arg_str(function, args, i)?)?;
+            if let Some(found) = regex.captures(input) {
+                let mut captures = Captures::new();
+                for (group, name) in regex.capture_names().enumerate() {
+                    let value = found.get(group).map_or(Value::Null, |m| Value::from(m.as_str()));
+                    if let Some(name) = name {
+                        captures.insert(name.to_owned(), value.clone());
+                    }
+                    captures.insert(group.to_string(), value);
+                }
+                return Ok(Some(captures));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A one line message for a pattern that does not compile; the syntax errors of `regex` span several lines.
+pub fn invalid_pattern(pattern: &str, e: &regex::Error) -> String {
+    let message = e.to_string();
+    let reason = message.lines().last().unwrap_or_default();
+    format!("invalid pattern [{}]: {}", pattern, reason.strip_prefix("error: ").unwrap_or(reason))
+}
+
+/// Whether a function takes regular expressions as arguments.
+pub fn takes_patterns(function: SingleInputFunction) -> bool {
+    matches!(function, SingleInputFunction::Matches | SingleInputFunction::NotMatches | SingleInputFunction::MatchesAny)
+}
+
+/// Whether the groups of a function's patterns can be used in the output.
+pub fn captures_groups(function: SingleInputFunction) -> bool {
+    matches!(function, SingleInputFunction::Matches | SingleInputFunction::MatchesAny)
+}
+
+// True when one of the patterns in `args` matches somewhere in the input.
+fn any_match(patterns: &Patterns, function: SingleInputFunction, input: &Value, args: &[Value]) -> Result<bool> {
+    let input = input_str(function, input)?;
+    for i in 0..args.len() {
+        if patterns.compiled(function, arg_str(function, args, i)?)?.is_match(input) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+pub fn default_pattern_functions(m: &mut HashMap<SingleInputFunction, Function>, patterns: &Patterns) {
+    let shared = patterns.clone();
+    m.insert(SingleInputFunction::Matches, Box::new(move |input, args| {
+        check_args(SingleInputFunction::Matches, args, 1)?;
+        any_match(&shared, SingleInputFunction::Matches, input, args)
+    }));
+
+    let shared = patterns.clone();
+    m.insert(SingleInputFunction::NotMatches, Box::new(move |input, args| {
+        check_args(SingleInputFunction::NotMatches, args, 1)?;
+        Ok(!any_match(&shared, SingleInputFunction::NotMatches, input, args)?)
+    }));
+
+    let shared = patterns.clone();
+    m.insert(SingleInputFunction::MatchesAny, Box::new(move |input, args| {
+        any_match(&shared, SingleInputFunction::MatchesAny, input, args)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn match_patterns() {
+        let patterns = Patterns::default();
+        let mut functions = HashMap::new();
+        default_pattern_functions(&mut functions, &patterns);
+        let call = |function: SingleInputFunction, input: Value, args: Value| functions[&function](&input, args.as_array().unwrap());
+
+        assert!(call(SingleInputFunction::Matches, json!("SKU-1234"), json!([r"^SKU-\d{4}$"])).unwrap());
+        assert!(!call(SingleInputFunction::Matches, json!("SKU-12345"), json!([r"^SKU-\d{4}$"])).unwrap());
+        assert!(call(SingleInputFunction::Matches, json!("drop table users"), json!(["(?i)DROP TABLE"])).unwrap());
+        assert!(call(SingleInputFunction::NotMatches, json!("hello"), json!(["<script"])).unwrap());
+        assert!(call(SingleInputFunction::MatchesAny, json!("B-7"), json!([r"^A-\d$", r"^B-\d$"])).unwrap());
+        assert!(!call(SingleInputFunction::MatchesAny, json!("C-7"), json!([r"^A-\d$", r"^B-\d$"])).unwrap());
+
+        assert_eq!(call(SingleInputFunction::Matches, json!(12), json!(["1"])).unwrap_err().to_string(),
+            "[Matches]: input is not a string, found number");
+        assert_eq!(call(SingleInputFunction::Matches, json!("a"), json!(["("])).unwrap_err().to_string(),
+            "[Matches]: invalid pattern [(]: unclosed group");
+
+        // Every pattern was compiled once.
+        call(SingleInputFunction::Matches, json!("SKU-1"), json!([r"^SKU-\d{4}$"])).unwrap();
+        assert_eq!(patterns.len(), 5);
+    }
+
+    #[test]
+    fn keep_the_patterns_used_last() {
+        let patterns = Patterns::with_capacity(2);
+        let a = patterns.get("a").unwrap();
+        patterns.get("b").unwrap();
+        patterns.get("a").unwrap();
+        patterns.get("c").unwrap();
+
+        assert_eq!(patterns.len(), 2);
+        assert!(Arc::ptr_eq(&a, &patterns.get("a").unwrap()));
+        // `b` was dropped for `c`, and is compiled again in place of `c`.
+        patterns.get("b").unwrap();
+        assert_eq!(patterns.len(), 2);
+        assert!(Arc::ptr_eq(&a, &patterns.get("a").unwrap()));
+    }
+
+    #[test]
+    fn capture_groups() {
+        let patterns = Patterns::default();
+        let captures = patterns.captures(SingleInputFunction::MatchesAny, &json!("SKU-42-XL"), &[json!("^A"), json!(r"^SKU-(?P<id>\d+)(-(\w+))?(-(\d))?")])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(captures["0"], json!("SKU-42-XL"));
+        assert_eq!(captures["id"], json!("42"));
+        assert_eq!(captures["1"], json!("42"));
+        assert_eq!(captures["3"], json!("XL"));
+        assert_eq!(captures["5"], json!(null));
+        assert!(patterns.captures(SingleInputFunction::Matches, &json!("x"), &[json!("y")]).unwrap().is_none());
+    }
+}
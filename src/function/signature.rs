@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::aggregator::Aggregator;
+use super::list_function::{list_function_constraints_argument_number, ListFunction};
+use super::numeric::Number;
+use super::SingleInputFunction;
+
+/// The kind of value a function takes as input or arguments. `Number` also accepts strings holding a number,
+/// `NumberOrNull` and `StringOrNull` also `null`, e.g. for an open range bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueType {
+    Any,
+    Number,
+    NumberOrNull,
+    String,
+    StringOrNull,
+    List,
+}
+
+impl ValueType {
+    pub fn accepts(self, value: &Value) -> bool {
+        match self {
+            ValueType::Any => true,
+            ValueType::Number => Number::from_value(value).is_some(),
+            ValueType::NumberOrNull => value.is_null() || Number::from_value(value).is_some(),
+            ValueType::String => value.is_string(),
+            ValueType::StringOrNull => value.is_null() || value.is_string(),
+            ValueType::List => value.is_array(),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Any => "any",
+            ValueType::Number => "number",
+            ValueType::NumberOrNull => "number or null",
+            ValueType::String => "string",
+            ValueType::StringOrNull => "string or null",
+            ValueType::List => "list",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How many arguments a function takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(self, count: usize) -> bool {
+        match self {
+            Arity::Exactly(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exactly(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+        }
+    }
+}
+
+/// Where a function is used in a rule: on a `single` value or list element, as the `constraint` of a `list` condition,
+/// or as the `aggregator` of an `aggregate` condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FunctionKind {
+    Single,
+    List,
+    Aggregate,
+}
+
+/// What a function is called and what it expects, for validation and for tools that write rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Signature {
+    pub name: &'static str,
+    pub kind: FunctionKind,
+    /// Other names the function can be written with in a rule file.
+    pub aliases: &'static [&'static str],
+    pub input: ValueType,
+    pub args: Arity,
+    pub arg_type: ValueType,
+    pub description: &'static str,
+    /// The result of the function when the value at the input path is missing. A condition on a missing value is
+    /// false at the first function whose result is `false`, and fails with the missing path at the first function
+    /// without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_missing: Option<bool>,
+}
+
+/// The signatures of the functions of a `FunctionRegistry`, keyed like its functions.
+#[derive(Debug, Clone, Default)]
+pub struct Signatures {
+    pub single: HashMap<SingleInputFunction, Signature>,
+    pub list: HashMap<ListFunction, Signature>,
+    pub aggregate: HashMap<Aggregator, Signature>,
+}
+
+pub fn default_signatures() -> Signatures {
+    Signatures {
+        single: default_single_signatures(),
+        list: default_list_signatures(),
+        aggregate: default_aggregate_signatures(),
+    }
+}
+
+fn single(
+    name: &'static str,
+    aliases: &'static [&'static str],
+    input: ValueType,
+    args: Arity,
+    arg_type: ValueType,
+    description: &'static str,
+) -> Signature {
+    Signature { name, kind: FunctionKind::Single, aliases, input, args, arg_type, description, on_missing: None }
+}
+
+// A test of the input's presence or type, which is false when the input is missing.
+fn predicate(name: &'static str, aliases: &'static [&'static str], description: &'static str) -> Signature {
+    Signature { on_missing: Some(false), ..single(name, aliases, ValueType::Any, Arity::Exactly(0), ValueType::Any, description) }
+}
+
+fn default_single_signatures() -> HashMap<SingleInputFunction, Signature> {
+    use Arity::*;
+    use SingleInputFunction::*;
+    use ValueType::{Any, Number, NumberOrNull, String, StringOrNull};
+
+    HashMap::from([
+        (Empty, single("Empty", &["empty"], Any, Exactly(0), Any, "The input is an empty string, list or object.")),
+        (NonEmpty, single("NonEmpty", &["non_empty"], Any, Exactly(0), Any, "The input is a string, list or object that is not empty.")),
+        (Equal, single("Equal", &["equal", "eq", "=="], Any, Exactly(1), Any, "The input equals the argument; numbers are compared by value.")),
+        (NotEqual, single("NotEqual", &["not_equal", "ne", "!="], Any, Exactly(1), Any, "The input does not equal the argument; numbers are compared by value.")),
+        (Greater, single("Greater", &["greater", "gt", ">"], Number, Exactly(1), Number, "The input is greater than the argument.")),
+        (GreaterEq, single("GreaterEq", &["greater_eq", "gte", ">="], Number, Exactly(1), Number, "The input is greater than or equal to the argument.")),
+        (Lower, single("Lower", &["lower", "lt", "<"], Number, Exactly(1), Number, "The input is lower than the argument.")),
+        (LowerEq, single("LowerEq", &["lower_eq", "lte", "<="], Number, Exactly(1), Number, "The input is lower than or equal to the argument.")),
+        (Between, single("Between", &["between"], Number, Exactly(2), NumberOrNull, "The input lies between the two arguments, bounds excluded; a null bound leaves the range open.")),
+        (BetweenEq, single("BetweenEq", &["between_eq"], Number, Exactly(2), NumberOrNull, "The input lies between the two arguments, bounds included; a null bound leaves the range open.")),
+        (NotBetween, single("NotBetween", &["not_between"], Number, Exactly(2), NumberOrNull, "The input does not lie between the two arguments, bounds excluded, so it may be on a bound.")),
+        (NotBetweenEq, single("NotBetweenEq", &["not_between_eq"], Number, Exactly(2), NumberOrNull, "The input does not lie between the two arguments, bounds included.")),
+        (EqualIgnoreCase, single("EqualIgnoreCase", &["equal_ignore_case"], String, Exactly(1), String, "The input equals the argument, ignoring case.")),
+        (EqualAny, single("EqualAny", &["equal_any", "in"], Any, AtLeast(1), Any, "The input equals one of the arguments.")),
+        (EqualAnyIgnoreCase, single("EqualAnyIgnoreCase", &["equal_any_ignore_case"], String, AtLeast(1), String, "The input equals one of the arguments, ignoring case.")),
+        (NotEqualAny, single("NotEqualAny", &["not_equal_any", "not_in"], Any, AtLeast(1), Any, "The input equals none of the arguments.")),
+        (StartsWith, single("StartsWith", &["starts_with"], String, Exactly(1), String, "The input starts with the argument.")),
+        (StartsWithIgnoreCase, single("StartsWithIgnoreCase", &["starts_with_ignore_case"], String, Exactly(1), String, "The input starts with the argument, ignoring case.")),
+        (EndsWith, single("EndsWith", &["ends_with"], String, Exactly(1), String, "The input ends with the argument.")),
+        (EndsWithIgnoreCase, single("EndsWithIgnoreCase", &["ends_with_ignore_case"], String, Exactly(1), String, "The input ends with the argument, ignoring case.")),
+        (Contains, single("Contains", &["contains"], String, Exactly(1), String, "The input contains the argument.")),
+        (ContainsIgnoreCase, single("ContainsIgnoreCase", &["contains_ignore_case"], String, Exactly(1), String, "The input contains the argument, ignoring case.")),
+        (Matches, single("Matches", &["matches", "~"], String, Exactly(1), String, "The regular expression in the argument matches the input; its groups can be used in the output.")),
+        (NotMatches, single("NotMatches", &["not_matches", "!~"], String, Exactly(1), String, "The regular expression in the argument does not match the input.")),
+        (MatchesAny, single("MatchesAny", &["matches_any"], String, AtLeast(1), String, "One of the regular expressions in the arguments matches the input; the groups of the first one can be used in the output.")),
+        (Before, single("Before", &["before"], String, Exactly(1), String, "The input date is before the argument.")),
+        (After, single("After", &["after"], String, Exactly(1), String, "The input date is after the argument.")),
+        (BetweenDates, single("BetweenDates", &["between_dates"], String, Exactly(2), StringOrNull, "The input date lies between the two arguments, bounds included; a date as upper bound includes the whole day and a null bound leaves the range open.")),
+        (WithinLast, single("WithinLast", &["within_last"], String, Exactly(1), String, "The input date lies within the duration in the argument before now, e.g. `30d`.")),
+        (OlderThan, single("OlderThan", &["older_than"], String, Exactly(1), String, "The input date is at least the duration in the argument before now, e.g. `18y`.")),
+        (DayOfWeek, single("DayOfWeek", &["day_of_week"], String, AtLeast(1), String, "The input date falls on one of the days of the week in the arguments, e.g. `Sat`.")),
+        (BusinessHours, single("BusinessHours", &["business_hours"], String, Exactly(2), String, "The input date falls on a weekday, from the opening time in the first argument to the closing time in the second, e.g. `09:00`.")),
+        (Exists, predicate("Exists", &["exists"], "There is a value at the input path, which may be null.")),
+        (IsNull, predicate("IsNull", &["is_null"], "The input is null; false when there is no value at the input path.")),
+        (IsNumber, predicate("IsNumber", &["is_number"], "The input is a number, not a string holding one.")),
+        (IsBoolean, predicate("IsBoolean", &["is_boolean"], "The input is true or false.")),
+        (IsList, predicate("IsList", &["is_list"], "The input is a list.")),
+    ])
+}
+
+fn default_list_signatures() -> HashMap<ListFunction, Signature> {
+    use ListFunction::*;
+
+    [
+        (All, "All", &["all"][..], "Every element passed."),
+        (AtLeast, "AtLeast", &["at_least"], "At least as many elements as the argument passed."),
+        (AtMost, "AtMost", &["at_most"], "At most as many elements as the argument passed."),
+        (Exactly, "Exactly", &["exactly"], "Exactly as many elements as the argument passed."),
+        (NoneElement, "NoneElement", &["none"], "No element passed."),
+        (AtLeastFraction, "AtLeastFraction", &["at_least_fraction"], "At least the fraction numerator / denominator of the elements passed."),
+        (AtMostFraction, "AtMostFraction", &["at_most_fraction"], "At most the fraction numerator / denominator of the elements passed."),
+    ]
+    .into_iter()
+    .map(|(function, name, aliases, description)| {
+        let args = Arity::Exactly(list_function_constraints_argument_number(function) as usize);
+        let signature = Signature { name, kind: FunctionKind::List, aliases, input: ValueType::List, args, arg_type: ValueType::Number, description, on_missing: None };
+        (function, signature)
+    })
+    .collect()
+}
+
+fn default_aggregate_signatures() -> HashMap<Aggregator, Signature> {
+    use Aggregator::*;
+
+    [
+        (SUM, "SUM", &["sum"][..], "The sum of the elements, 0 for an empty list."),
+        (MIN, "MIN", &["min"], "The smallest element."),
+        (MAX, "MAX", &["max"], "The largest element."),
+        (AVG, "AVG", &["avg"], "The average of the elements."),
+        (COUNT, "COUNT", &["count"], "The number of elements, of any type."),
+    ]
+    .into_iter()
+    .map(|(aggregator, name, aliases, description)| {
+        let signature = Signature { name, kind: FunctionKind::Aggregate, aliases, input: ValueType::List, args: Arity::Exactly(0), arg_type: ValueType::Any, description, on_missing: None };
+        (aggregator, signature)
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+    use std::fmt::Debug;
+    use std::hash::Hash;
+
+    fn check_names<T: DeserializeOwned + Debug + Eq + Hash>(signatures: &HashMap<T, Signature>) {
+        for (function, signature) in signatures {
+            assert_eq!(format!("{:?}", function), signature.name);
+            for name in std::iter::once(&signature.name).chain(signature.aliases) {
+                assert_eq!(&serde_json::from_value::<T>(json!(name)).unwrap(), function, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn names_and_aliases_can_be_read() {
+        let signatures = default_signatures();
+        assert_eq!(signatures.single.len(), super::super::FunctionRegistry::default().single.len());
+
+        check_names(&signatures.single);
+        check_names(&signatures.list);
+        check_names(&signatures.aggregate);
+    }
+
+    #[test]
+    fn serialize_signature() {
+        let signatures = default_signatures();
+        assert_eq!(serde_json::to_value(&signatures.single[&SingleInputFunction::Between]).unwrap(), json!({
+            "name": "Between",
+            "kind": "single",
+            "aliases": ["between"],
+            "input": "number",
+            "args": {"exactly": 2},
+            "arg_type": "number_or_null",
+            "description": "The input lies between the two arguments, bounds excluded; a null bound leaves the range open.",
+        }));
+        assert_eq!(serde_json::to_value(signatures.list[&ListFunction::AtLeast].args).unwrap(), json!({"exactly": 1}));
+        assert_eq!(serde_json::to_value(Arity::AtLeast(1)).unwrap(), json!({"at_least": 1}));
+        let is_null = serde_json::to_value(&signatures.single[&SingleInputFunction::IsNull]).unwrap();
+        assert_eq!((&is_null["input"], &is_null["on_missing"]), (&json!("any"), &json!(false)));
+    }
+}
@@ -0,0 +1,250 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use caseless::default_case_fold_str;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use super::{arg_str, check_args, input_str, Function, SingleInputFunction};
+use crate::error::{LogicLoomError, Result};
+
+/// The Unicode normalization strings are brought to before they are compared.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Normalization {
+    /// Strings are compared as they are, so a precomposed `é` differs from `e` followed by a combining accent.
+    #[default]
+    None,
+    /// Canonically equivalent strings are equal: the two forms of `é` above.
+    Nfc,
+    /// Compatibility equivalent strings are equal too, e.g. the ligature `ﬁ` and `fi`, or `²` and `2`.
+    Nfkc,
+}
+
+/// How the string functions, and the `Equal` functions for two strings, compare text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextOptions {
+    #[serde(default)]
+    pub normalization: Normalization,
+    /// Accents and other combining marks are ignored, so `Müller` equals `Muller` and `İ` equals `I`.
+    #[serde(default)]
+    pub ignore_accents: bool,
+    /// The `*IgnoreCase` functions fold case the Turkish and Azerbaijani way: `İ` equals `i` and `I` equals `ı`, so
+    /// `I` no longer equals `i`.
+    #[serde(default)]
+    pub turkic: bool,
+}
+
+impl TextOptions {
+    /// The form a string is compared in by the case-sensitive functions.
+    pub fn normalize<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        match (self.normalization, self.ignore_accents) {
+            (Normalization::None, false) => Cow::Borrowed(s),
+            (Normalization::Nfc, false) => Cow::Owned(s.nfc().collect()),
+            (Normalization::Nfkc, false) => Cow::Owned(s.nfkc().collect()),
+            (Normalization::Nfkc, true) => Cow::Owned(s.nfkd().filter(|c| !is_combining_mark(*c)).nfkc().collect()),
+            (_, true) => Cow::Owned(s.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect()),
+        }
+    }
+
+    /// The form a string is compared in by the `*IgnoreCase` functions: fully case folded, so `ß` equals `SS` and
+    /// `ς` equals `Σ`, and normalized as set. With a normalization, this is the canonical or compatibility caseless
+    /// match of the Unicode standard.
+    pub fn fold(&self, s: &str) -> String {
+        let s = match self.turkic {
+            true => Cow::Owned(turkic_lowercase(s)),
+            false => Cow::Borrowed(s),
+        };
+        let s = s.as_ref();
+        let folded = match self.normalization {
+            Normalization::None => default_case_fold_str(s),
+            Normalization::Nfc => default_case_fold_str(&s.nfd().collect::<String>()),
+            Normalization::Nfkc => {
+                let folded = default_case_fold_str(&s.nfd().collect::<String>());
+                default_case_fold_str(&folded.nfkd().collect::<String>())
+            }
+        };
+        match self.normalize(&folded) {
+            Cow::Borrowed(_) => folded,
+            Cow::Owned(normalized) => normalized,
+        }
+    }
+
+    fn key<'a>(&self, s: &'a str, ignore_case: bool) -> Cow<'a, str> {
+        match ignore_case {
+            true => Cow::Owned(self.fold(s)),
+            false => self.normalize(s),
+        }
+    }
+}
+
+// Lowercases the letters the Turkic languages case differently from the default folding, which folds the rest:
+// `İ`, also written as `I` with a combining dot above, to `i`, and `I` to the dotless `ı`.
+fn turkic_lowercase(s: &str) -> String {
+    let mut lowercased = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            'İ' => lowercased.push('i'),
+            'I' if chars.next_if_eq(&'\u{307}').is_some() => lowercased.push('i'),
+            'I' => lowercased.push('ı'),
+            c => lowercased.push(c),
+        }
+    }
+    lowercased
+}
+
+// A function of one string argument, comparing the input and the argument in the form `options` give.
+fn insert(
+    m: &mut HashMap<SingleInputFunction, Function>,
+    function: SingleInputFunction,
+    options: TextOptions,
+    ignore_case: bool,
+    test: fn(&str, &str) -> bool,
+) {
+    m.insert(function, Box::new(move |input: &Value, args: &[Value]| {
+        check_args(function, args, 1)?;
+        let input = input_str(function, input)?;
+        let arg = arg_str(function, args, 0)?;
+        Ok(test(&options.key(input, ignore_case), &options.key(arg, ignore_case)))
+    }));
+}
+
+pub fn default_text_functions(m: &mut HashMap<SingleInputFunction, Function>, options: TextOptions) {
+    insert(m, SingleInputFunction::EqualIgnoreCase, options, true, |input, arg| input == arg);
+    insert(m, SingleInputFunction::StartsWith, options, false, |input, arg| input.starts_with(arg));
+    insert(m, SingleInputFunction::StartsWithIgnoreCase, options, true, |input, arg| input.starts_with(arg));
+    insert(m, SingleInputFunction::EndsWith, options, false, |input, arg| input.ends_with(arg));
+    insert(m, SingleInputFunction::EndsWithIgnoreCase, options, true, |input, arg| input.ends_with(arg));
+    insert(m, SingleInputFunction::Contains, options, false, |input, arg| input.contains(arg));
+    insert(m, SingleInputFunction::ContainsIgnoreCase, options, true, |input, arg| input.contains(arg));
+
+    m.insert(SingleInputFunction::EqualAnyIgnoreCase, Box::new(move |input, args| {
+        let function = SingleInputFunction::EqualAnyIgnoreCase;
+        let input = options.fold(input_str(function, input)?);
+        if args.is_empty() {
+            return Err(LogicLoomError::argument_count(function, 1, 0));
+        }
+        let args = (0..args.len()).map(|i| arg_str(function, args, i)).collect::<Result<Vec<_>>>()?;
+        Ok(args.into_iter().any(|arg| options.fold(arg) == input))
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn functions(normalization: Normalization, ignore_accents: bool) -> HashMap<SingleInputFunction, Function> {
+        with_options(TextOptions { normalization, ignore_accents, ..TextOptions::default() })
+    }
+
+    fn with_options(options: TextOptions) -> HashMap<SingleInputFunction, Function> {
+        let mut m = HashMap::new();
+        default_text_functions(&mut m, options);
+        m
+    }
+
+    fn holds(functions: &HashMap<SingleInputFunction, Function>, function: SingleInputFunction, input: &str, args: &[&str]) -> bool {
+        let args: Vec<Value> = args.iter().map(|arg| json!(arg)).collect();
+        functions[&function](&json!(input), &args).unwrap()
+    }
+
+    #[test]
+    fn full_case_folding() {
+        use SingleInputFunction::*;
+        let f = functions(Normalization::None, false);
+
+        assert!(holds(&f, EqualIgnoreCase, "ÄRGER", &["ärger"]));
+        assert!(holds(&f, EqualIgnoreCase, "Straße", &["STRASSE"]));
+        assert!(holds(&f, EqualIgnoreCase, "ΟΔΥΣΣΕΥΣ", &["οδυσσευς"]));
+        assert!(holds(&f, StartsWithIgnoreCase, "STRASSENBAHN", &["straße"]));
+        assert!(holds(&f, EndsWithIgnoreCase, "Großstraße", &["STRASSE"]));
+        assert!(holds(&f, ContainsIgnoreCase, "Émile ZOLA", &["émile"]));
+        assert!(holds(&f, EqualAnyIgnoreCase, "ÇAĞLAR", &["x", "çağlar"]));
+        assert!(!holds(&f, EqualIgnoreCase, "Ä", &["A"]));
+        // Without Turkic folding, `İ` folds to `i` with a combining dot, and `ı` stays dotless.
+        assert!(!holds(&f, EqualIgnoreCase, "İstanbul", &["istanbul"]));
+        assert!(!holds(&f, EqualIgnoreCase, "ılık", &["ILIK"]));
+        assert!(holds(&f, EqualIgnoreCase, "İSTANBUL", &["İstanbul"]));
+
+        // Case-sensitive functions are unchanged.
+        assert!(holds(&f, StartsWith, "Straße", &["Stra"]));
+        assert!(!holds(&f, Contains, "Straße", &["SS"]));
+        // Full case folding also splits ligatures.
+        assert!(holds(&f, EqualIgnoreCase, "ﬁle", &["FILE"]));
+    }
+
+    #[test]
+    fn normalization() {
+        use SingleInputFunction::*;
+        let (composed, decomposed) = ("Café", "Cafe\u{301}");
+
+        let f = functions(Normalization::None, false);
+        assert!(!holds(&f, EqualIgnoreCase, composed, &[decomposed]));
+        assert!(!holds(&f, EndsWith, composed, &["e\u{301}"]));
+
+        let f = functions(Normalization::Nfc, false);
+        assert!(holds(&f, EqualIgnoreCase, composed, &[decomposed]));
+        assert!(holds(&f, EndsWith, composed, &["e\u{301}"]));
+        // A base letter does not match the start of an accented one.
+        assert!(!holds(&f, EndsWith, composed, &["e"]));
+        assert!(!holds(&f, EqualIgnoreCase, "M²", &["m2"]));
+
+        let f = functions(Normalization::Nfkc, false);
+        assert!(holds(&f, EqualIgnoreCase, "M²", &["m2"]));
+        assert!(holds(&f, StartsWith, "ﬁle", &["fi"]));
+        assert!(holds(&f, Contains, "m²", &["m2"]));
+        assert!(holds(&f, StartsWithIgnoreCase, "Ｆｕｌｌ width", &["full"]));
+    }
+
+    #[test]
+    fn ignore_accents() {
+        use SingleInputFunction::*;
+        let f = functions(Normalization::None, true);
+
+        assert!(holds(&f, EqualIgnoreCase, "Müller", &["MULLER"]));
+        assert!(holds(&f, Contains, "Crème brûlée", &["Creme brulee"]));
+        assert!(!holds(&f, Contains, "Crème brûlée", &["creme"]));
+        assert!(holds(&f, EqualIgnoreCase, "İstanbul", &["ISTANBUL"]));
+        assert!(holds(&f, EqualAnyIgnoreCase, "Şişli", &["sisli"]));
+        assert!(holds(&f, EqualIgnoreCase, "Cafe\u{301}", &["CAFE"]));
+
+        let f = functions(Normalization::Nfkc, true);
+        assert!(holds(&f, EqualIgnoreCase, "ﬁancé", &["FIANCE"]));
+    }
+
+    #[test]
+    fn turkic_case_folding() {
+        use SingleInputFunction::*;
+        let f = with_options(TextOptions { turkic: true, ..TextOptions::default() });
+
+        assert!(holds(&f, EqualIgnoreCase, "İstanbul", &["istanbul"]));
+        assert!(holds(&f, EqualIgnoreCase, "ılık", &["ILIK"]));
+        assert!(holds(&f, EqualIgnoreCase, "I\u{307}zmir", &["izmir"]));
+        assert!(!holds(&f, EqualIgnoreCase, "ISTANBUL", &["istanbul"]));
+        assert!(holds(&f, StartsWithIgnoreCase, "DİYARBAKIR", &["diyarbakır"]));
+        // The other letters fold as usual.
+        assert!(holds(&f, EqualAnyIgnoreCase, "ŞIŞLI", &["x", "şışlı"]));
+
+        let f = with_options(TextOptions { turkic: true, normalization: Normalization::Nfc, ..TextOptions::default() });
+        assert!(holds(&f, EqualIgnoreCase, "İstanbul", &["istanbul"]));
+        assert!(holds(&f, EqualIgnoreCase, "I\u{307}STANBUL", &["istanbul"]));
+    }
+
+    #[test]
+    fn errors() {
+        let f = functions(Normalization::Nfc, true);
+
+        let err = f[&SingleInputFunction::ContainsIgnoreCase](&json!("abc"), &[]).unwrap_err();
+        assert_eq!(err.to_string(), "[ContainsIgnoreCase]: expected 1 argument but got 0");
+        let err = f[&SingleInputFunction::EqualIgnoreCase](&json!(1), &[json!("1")]).unwrap_err();
+        assert_eq!(err.to_string(), "[EqualIgnoreCase]: input is not a string, found number");
+
+        let err = f[&SingleInputFunction::EqualAnyIgnoreCase](&json!("abc"), &[]).unwrap_err();
+        assert_eq!(err.to_string(), "[EqualAnyIgnoreCase]: expected 1 argument but got 0");
+        let err = f[&SingleInputFunction::EqualAnyIgnoreCase](&json!("abc"), &[json!("ABC"), json!(1)]).unwrap_err();
+        assert_eq!(err.to_string(), "[EqualAnyIgnoreCase]: argument [1] is not a string, found number");
+    }
+}
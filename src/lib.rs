@@ -0,0 +1,13 @@
+pub mod condition;
+pub mod core;
+pub mod error;
+pub mod function;
+pub mod loader;
+pub mod output;
+pub mod path;
+pub mod rules;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod template;
+pub mod trace;
+pub mod validate;
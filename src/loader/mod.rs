@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_path_to_error::Segment;
+
+use crate::core::{HitPolicy, Rule, RuleGroup};
+use crate::error::{LogicLoomError, Result};
+use crate::function::FunctionRegistry;
+use crate::output::OutputMerge;
+use crate::validate::{validate_file, validate_group, validate_rule};
+
+/// The contents of a rule file: rule groups keyed by their name, and optionally how their outputs are merged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge: Option<OutputMerge>,
+    pub groups: BTreeMap<String, RuleGroup>,
+}
+
+impl RuleFile {
+    /// Adds the groups and merge settings of another file, the way `Engine::add_rule_file` registers them. A group
+    /// keeps its hit policy unless the other file sets one; nothing is added when the other file sets a different one.
+    pub fn extend(&mut self, other: RuleFile) -> Result<()> {
+        let conflicts = other.groups.iter()
+            .filter_map(|(name, group)| conflicting_hit_policy(name, self.groups.get(name)?.hit_policy, group.hit_policy))
+            .collect();
+        checked(conflicts, ())?;
+        if let Some(merge) = other.merge {
+            self.merge.get_or_insert_with(OutputMerge::default).extend(merge);
+        }
+        for (name, group) in other.groups {
+            let own = self.groups.entry(name).or_default();
+            own.hit_policy = group.hit_policy.or(own.hit_policy);
+            own.rules.extend(group.rules);
+        }
+        Ok(())
+    }
+}
+
+/// The problem with adding a group that sets the hit policy `added` to the group `name` with the hit policy `own`.
+pub(crate) fn conflicting_hit_policy(name: &str, own: Option<HitPolicy>, added: Option<HitPolicy>) -> Option<LoadError> {
+    match (own, added) {
+        (Some(own), Some(added)) if own != added => Some(LoadError {
+            path: Some(format!("groups.{}.hit_policy", name)),
+            ..LoadError::new(format!("hit policy `{:?}` conflicts with `{:?}`, set for the group before", added, own))
+        }),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// A rule file that could not be loaded, with as much location as is known.
+#[derive(Debug)]
+pub struct LoadError {
+    pub file: Option<String>,
+    pub rule: Option<String>,
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl LoadError {
+    fn new(message: impl Into<String>) -> Self {
+        LoadError { file: None, rule: None, path: None, message: message.into() }
+    }
+
+    fn in_file(mut self, file: &Path) -> Self {
+        self.file = Some(file.display().to_string());
+        self
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file)?;
+        }
+        if let Some(rule) = &self.rule {
+            write!(f, "rule `{}`: ", rule)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, "{}: ", path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for LoadError {}
+
+/// Loads a rule file, picking the format from its extension (`.json`, `.yaml` or `.yml`). The rules are validated
+/// against `functions`, which should be the functions they will run with, e.g. `Engine::functions`.
+pub fn load_file(path: impl AsRef<Path>, functions: &FunctionRegistry) -> Result<RuleFile> {
+    let path = path.as_ref();
+    let format = Format::from_path(path)
+        .ok_or_else(|| LoadError::new("unknown rule file extension, expected .json, .yaml or .yml").in_file(path))?;
+    let source = fs::read_to_string(path)
+        .map_err(|e| LoadError::new(e.to_string()).in_file(path))?;
+    let file = parse(&source, format).map_err(|e| e.in_file(path))?;
+    checked(validate_file(&file, functions), file)
+        .map_err(|e| match e {
+            LogicLoomError::InvalidRules(diagnostics) => {
+                LogicLoomError::InvalidRules(diagnostics.into_iter().map(|diagnostic| diagnostic.in_file(path)).collect())
+            }
+            e => e,
+        })
+}
+
+/// Reads a rule file and validates it against `functions`, like `load_file`.
+pub fn load_str(source: &str, format: Format, functions: &FunctionRegistry) -> Result<RuleFile> {
+    let file = parse(source, format)?;
+    checked(validate_file(&file, functions), file)
+}
+
+/// Reads a single rule from a JSON document, with the same checks and error locations as a rule file.
+pub fn load_rule(document: &Value, functions: &FunctionRegistry) -> Result<Rule> {
+    let rule = deserialize(document, |_, _| document["name"].as_str().map(str::to_owned))?;
+    checked(validate_rule(&rule, functions), rule)
+}
+
+/// Reads a single rule group, i.e. `{"hit_policy": ..., "rules": [...]}`, from a JSON document.
+pub fn load_group(document: &Value, functions: &FunctionRegistry) -> Result<RuleGroup> {
+    let group = deserialize(document, |document, path| group_rule_name(document, &mut path.iter()))?;
+    checked(validate_group(&group, functions), group)
+}
+
+fn checked<T>(diagnostics: Vec<LoadError>, value: T) -> Result<T> {
+    match diagnostics.is_empty() {
+        true => Ok(value),
+        false => Err(LogicLoomError::InvalidRules(diagnostics)),
+    }
+}
+
+fn parse(source: &str, format: Format) -> std::result::Result<RuleFile, LoadError> {
+    // Both formats are read into a JSON document first so errors are located the same way.
+    let document: Value = match format {
+        Format::Json => serde_json::from_str(source).map_err(|e| LoadError::new(e.to_string()))?,
+        Format::Yaml => serde_yaml::from_str(source).map_err(|e| LoadError::new(e.to_string()))?,
+    };
+
+    deserialize(&document, rule_name)
+}
+
+fn deserialize<T: DeserializeOwned>(
+    document: &Value,
+    rule_name: impl Fn(&Value, &serde_path_to_error::Path) -> Option<String>,
+) -> std::result::Result<T, LoadError> {
+    serde_path_to_error::deserialize(document).map_err(|e| {
+        let rule = rule_name(document, e.path());
+        let path = e.path().to_string();
+        LoadError { file: None, rule, path: Some(path), message: e.into_inner().to_string() }
+    })
+}
+
+// Finds the name of the rule a failing field path points into, i.e. `groups.<group>.rules[<index>]`.
+fn rule_name(document: &Value, path: &serde_path_to_error::Path) -> Option<String> {
+    let mut segments = path.iter();
+    let group = match (segments.next()?, segments.next()?) {
+        (Segment::Map { key }, Segment::Map { key: group }) if key == "groups" => group,
+        _ => return None,
+    };
+    group_rule_name(&document["groups"][group], &mut segments)
+}
+
+// Like `rule_name`, for the `rules[<index>]` path within a group.
+fn group_rule_name<'a>(group: &Value, segments: &mut impl Iterator<Item = &'a Segment>) -> Option<String> {
+    let index = match (segments.next()?, segments.next()?) {
+        (Segment::Map { key }, Segment::Seq { index }) if key == "rules" => *index,
+        _ => return None,
+    };
+    group["rules"][index]["name"].as_str().map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::condition::{ConditionExpr, LogicalOperator};
+    use crate::function::SingleInputFunction;
+    use crate::output::MergeStrategy;
+
+    const RULES_YAML: &str = r#"
+groups:
+  eligibility:
+    rules:
+      - name: adult
+        conditions:
+          - single:
+              input_path: age
+              functions:
+                - function: GreaterEq
+                  args: [18]
+        output:
+          eligible: true
+      - name: vip
+        chain: OR
+        conditions:
+          - single:
+              input_path: tier
+              functions:
+                - function: EqualAnyIgnoreCase
+                  args: [gold, platinum]
+          - aggregate:
+              input_path: orders
+              aggregator: SUM
+              functions:
+                - function: Greater
+                  args: [10000]
+        output:
+          discount.percent: 10
+"#;
+
+    #[test]
+    fn load_yaml_rules() {
+        let file = load_str(RULES_YAML, Format::Yaml, &FunctionRegistry::default()).expect("expected rules to load");
+        let rules = &file.groups["eligibility"].rules;
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].name, "adult");
+        assert_eq!(rules[0].chain, LogicalOperator::AND);
+        assert_eq!(rules[0].output["eligible"], Value::Bool(true));
+        assert_eq!(rules[1].chain, LogicalOperator::OR);
+        assert!(matches!(&rules[1].conditions[0], ConditionExpr::Single(c)
+            if c.functions[0].function == SingleInputFunction::EqualAnyIgnoreCase && c.functions[0].args.len() == 2));
+        assert!(matches!(&rules[1].conditions[1], ConditionExpr::Aggregate(_)));
+    }
+
+    #[test]
+    fn load_json_rules() {
+        let source = r#"{"groups": {"pricing": {"rules": [
+            {"name": "bulk", "conditions": [{"single": {"input_path": "quantity", "functions": [{"function": "Greater", "args": ["10"]}]}}], "output": {"discount": 5}}
+        ]}}}"#;
+        let file = load_str(source, Format::Json, &FunctionRegistry::default()).expect("expected rules to load");
+
+        assert_eq!(file.groups["pricing"].rules[0].name, "bulk");
+    }
+
+    fn load_error(err: LogicLoomError) -> LoadError {
+        match err {
+            LogicLoomError::RuleLoad(err) => err,
+            other => panic!("expected a rule load error, got {}", other),
+        }
+    }
+
+    #[test]
+    fn load_error_reports_rule_and_field_path() {
+        let source = RULES_YAML.replace("EqualAnyIgnoreCase", "EqualsAny");
+        let err = load_error(load_str(&source, Format::Yaml, &FunctionRegistry::default()).unwrap_err());
+
+        assert_eq!(err.rule.as_deref(), Some("vip"));
+        assert_eq!(err.path.as_deref(), Some("groups.eligibility.rules[1].conditions[0].single.functions[0].function"));
+        assert!(err.to_string().starts_with("rule `vip`: groups.eligibility.rules[1]"));
+    }
+
+    #[test]
+    fn load_single_rules_and_groups() {
+        let rule = serde_json::json!({"name": "bulk", "conditions": [], "output": {"discount": 5}});
+        assert_eq!(load_rule(&rule, &FunctionRegistry::default()).unwrap().name, "bulk");
+
+        let group = serde_json::json!({"hit_policy": "First", "rules": [rule, {"name": "typo", "condition": []}]});
+        let err = load_error(load_group(&group, &FunctionRegistry::default()).unwrap_err());
+        assert_eq!(err.rule.as_deref(), Some("typo"));
+        assert_eq!(err.path.as_deref(), Some("rules[1].condition"));
+
+        let err = load_error(load_rule(&serde_json::json!({"name": "bulk", "conditions": {}}), &FunctionRegistry::default()).unwrap_err());
+        assert_eq!(err.to_string(), "rule `bulk`: conditions: invalid type: map, expected a sequence");
+    }
+
+    #[test]
+    fn extend_keeps_hit_policies() {
+        let file = |source: &str| load_str(source, Format::Yaml, &FunctionRegistry::default()).unwrap();
+        let mut rules = file("groups: {pricing: {hit_policy: First, rules: [{name: a, conditions: []}]}}");
+
+        rules.extend(file("groups: {pricing: {rules: [{name: b, conditions: []}]}}")).unwrap();
+        assert_eq!(rules.groups["pricing"].hit_policy, Some(HitPolicy::First));
+        assert_eq!(rules.groups["pricing"].rules.len(), 2);
+        rules.extend(file("groups: {pricing: {hit_policy: First, rules: []}}")).unwrap();
+
+        let err = rules.extend(file("groups: {pricing: {hit_policy: Collect, rules: [{name: c, conditions: []}]}}")).unwrap_err();
+        assert_eq!(err.to_string(), "groups.pricing.hit_policy: hit policy `Collect` conflicts with `First`, set for the group before");
+        assert_eq!(rules.groups["pricing"].rules.len(), 2);
+    }
+
+    #[test]
+    fn extend_keeps_the_default_merge_strategy() {
+        let file = |source: &str| load_str(source, Format::Yaml, &FunctionRegistry::default()).unwrap();
+        let mut rules = file("{merge: {default: LastWins}, groups: {}}");
+
+        rules.extend(file("{merge: {paths: {tags: ArrayUnion}}, groups: {}}")).unwrap();
+        let merge = rules.merge.unwrap();
+        assert_eq!(merge.default, Some(MergeStrategy::LastWins));
+        assert_eq!(merge.strategy("tags"), MergeStrategy::ArrayUnion);
+    }
+
+    #[test]
+    fn load_file_reports_file_name() {
+        let err = load_error(load_file("rules.toml", &FunctionRegistry::default()).unwrap_err());
+
+        assert_eq!(err.file.as_deref(), Some("rules.toml"));
+    }
+}
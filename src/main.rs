@@ -1,9 +1,317 @@
-mod condition;
-mod core;
-mod function;
-mod output;
-mod rules;
-
-fn main() {
-    println!("Hello, world!");
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
+
+use logicloom::core::HitPolicy;
+use logicloom::error::LogicLoomError;
+use logicloom::function::signature::FunctionKind;
+use logicloom::function::FunctionRegistry;
+use logicloom::loader::{load_file, Format};
+use logicloom::rules::engine::{Engine, Evaluation, GroupMerge};
+
+/// Evaluates LogicLoom rule files against JSON or YAML input documents.
+#[derive(Parser)]
+#[command(name = "logicloom", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Evaluates groups against an input document and prints the merged output.
+    Eval {
+        #[command(flatten)]
+        evaluation: EvalArgs,
+        /// How the outputs of several groups are combined; defaults to one object per group when there are several groups.
+        #[arg(long, value_enum)]
+        merge: Option<Merge>,
+    },
+    /// Checks that rule files can be loaded and that their rules are valid, e.g. call functions with the right arguments.
+    Validate {
+        /// Rule files (.json, .yaml or .yml).
+        #[arg(required = true)]
+        rules: Vec<PathBuf>,
+    },
+    /// Lists the groups and rules of rule files.
+    List {
+        /// Rule files (.json, .yaml or .yml).
+        #[arg(required = true)]
+        rules: Vec<PathBuf>,
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Evaluates groups against an input document and prints how every rule was evaluated.
+    Explain {
+        #[command(flatten)]
+        evaluation: EvalArgs,
+    },
+    /// Lists the functions rules can call, with their arguments.
+    Functions {
+        /// Only functions of this kind.
+        #[arg(short, long, value_enum)]
+        kind: Option<Kind>,
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Args)]
+struct EvalArgs {
+    /// Rule files (.json, .yaml or .yml).
+    #[arg(short, long = "rules", required = true)]
+    rules: Vec<PathBuf>,
+    /// Groups to evaluate.
+    #[arg(short, long = "group", required = true)]
+    groups: Vec<String>,
+    /// Input document, JSON or YAML. Read from stdin when missing or `-`.
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    #[arg(short, long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum OutputFormat {
+    #[default]
+    Json,
+    Yaml,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    Single,
+    List,
+    Aggregate,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Merge {
+    PerGroup,
+    Combined,
+}
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        // The reader went away, e.g. `logicloom list ... | head -1`; what it read was printed fine.
+        Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == ErrorKind::BrokenPipe) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> CliResult<ExitCode> {
+    match cli.command {
+        Command::Eval { evaluation: args, merge } => {
+            let engine = load_engine(&args.rules)?;
+            let input = read_input(args.input.as_deref())?;
+            let merge = match merge {
+                Some(Merge::PerGroup) => GroupMerge::PerGroup,
+                Some(Merge::Combined) => GroupMerge::Combined,
+                None if args.groups.len() == 1 => GroupMerge::Combined,
+                None => GroupMerge::PerGroup,
+            };
+            let groups: Vec<&str> = args.groups.iter().map(String::as_str).collect();
+            let evaluation = engine.evaluate_groups(&groups, &input, merge)?;
+            for error in &evaluation.errors {
+                eprintln!("warning: {}", error);
+            }
+            for conflict in &evaluation.conflicts {
+                eprintln!("warning: {}", conflict);
+            }
+            print(&evaluation.output, args.format)?;
+        }
+        Command::Validate { rules } => {
+            let mut valid = true;
+            for (file, problems) in rules.iter().zip(validate(&rules)) {
+                if problems.is_empty() {
+                    writeln!(io::stdout().lock(), "{}: ok", file.display())?;
+                }
+                for problem in &problems {
+                    eprintln!("error: {}", problem);
+                    valid = false;
+                }
+            }
+            if !valid {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::List { rules, format } => {
+            let engine = load_engine(&rules)?;
+            print(&list(&engine), format)?;
+        }
+        Command::Explain { evaluation: args } => {
+            let engine = load_engine(&args.rules)?;
+            let input = read_input(args.input.as_deref())?;
+            let mut evaluations = BTreeMap::new();
+            for group in &args.groups {
+                evaluations.insert(group.as_str(), engine.explain(group, &input)?);
+            }
+            match evaluations.len() {
+                1 => print(evaluations.values().next().unwrap(), args.format)?,
+                _ => print::<BTreeMap<&str, Evaluation>>(&evaluations, args.format)?,
+            }
+        }
+        Command::Functions { kind, format } => {
+            let functions = FunctionRegistry::default();
+            let signatures = match kind {
+                Some(Kind::Single) => functions.signatures_of(FunctionKind::Single),
+                Some(Kind::List) => functions.signatures_of(FunctionKind::List),
+                Some(Kind::Aggregate) => functions.signatures_of(FunctionKind::Aggregate),
+                None => functions.signatures(),
+            };
+            print(&signatures, format)?;
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn load_engine(files: &[PathBuf]) -> CliResult<Engine> {
+    let mut engine = Engine::new();
+    for file in files {
+        engine.add_rule_file(load_file(file, engine.functions())?)?;
+    }
+    Ok(engine)
+}
+
+// Checks rule files the way `load_engine` loads them, so that rules clashing with the rules of an earlier file are
+// reported too. Gives the problems of every file, none for a file that is ok.
+fn validate(files: &[PathBuf]) -> Vec<Vec<String>> {
+    let mut engine = Engine::new();
+    files.iter()
+        .map(|file| match load_file(file, engine.functions()).and_then(|rules| engine.add_rule_file(rules)) {
+            Ok(()) => Vec::new(),
+            Err(LogicLoomError::InvalidRules(diagnostics)) => diagnostics.into_iter()
+                .map(|mut diagnostic| {
+                    diagnostic.file.get_or_insert_with(|| file.display().to_string());
+                    diagnostic.to_string()
+                })
+                .collect(),
+            Err(e) => vec![e.to_string()],
+        })
+        .collect()
+}
+
+fn read_input(path: Option<&Path>) -> CliResult<Value> {
+    match path.filter(|path| path.as_os_str() != "-") {
+        Some(path) => {
+            let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            parse_input(&source, Format::from_path(path)).map_err(|e| format!("{}: {}", path.display(), e).into())
+        }
+        None => {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source)?;
+            parse_input(&source, None)
+        }
+    }
+}
+
+// Without a known format the input is read as JSON, and as YAML when it is not JSON.
+fn parse_input(source: &str, format: Option<Format>) -> CliResult<Value> {
+    Ok(match format {
+        Some(Format::Json) => serde_json::from_str(source)?,
+        Some(Format::Yaml) => serde_yaml::from_str(source)?,
+        None => match serde_json::from_str(source) {
+            Ok(value) => value,
+            Err(_) => serde_yaml::from_str(source)?,
+        },
+    })
+}
+
+#[derive(Serialize)]
+struct GroupListing<'a> {
+    hit_policy: HitPolicy,
+    rules: Vec<RuleListing<'a>>,
+}
+
+#[derive(Serialize)]
+struct RuleListing<'a> {
+    name: &'a str,
+    priority: i32,
+}
+
+fn list(engine: &Engine) -> BTreeMap<&str, GroupListing<'_>> {
+    engine.groups().into_iter()
+        .filter_map(|name| {
+            let group = engine.group(name)?;
+            let rules = group.rules.iter().map(|rule| RuleListing { name: &rule.name, priority: rule.priority }).collect();
+            Some((name, GroupListing { hit_policy: group.hit_policy(), rules }))
+        })
+        .collect()
+}
+
+// Writes instead of `println!`, which panics when stdout is closed.
+fn print<T: Serialize + ?Sized>(value: &T, format: OutputFormat) -> CliResult<()> {
+    let mut stdout = io::stdout().lock();
+    match format {
+        OutputFormat::Json => writeln!(stdout, "{}", serde_json::to_string_pretty(value)?)?,
+        OutputFormat::Yaml => write!(stdout, "{}", serde_yaml::to_string(value)?)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_json_and_yaml_input() {
+        let expected = json!({"customer": {"age": 30}});
+
+        assert_eq!(parse_input(r#"{"customer": {"age": 30}}"#, None).unwrap(), expected);
+        assert_eq!(parse_input("customer:\n  age: 30\n", None).unwrap(), expected);
+        assert_eq!(parse_input("customer:\n  age: 30\n", Some(Format::Yaml)).unwrap(), expected);
+        assert!(parse_input("customer:\n  age: 30\n", Some(Format::Json)).is_err());
+    }
+
+    #[test]
+    fn cli_arguments() {
+        let cli = Cli::try_parse_from(["logicloom", "eval", "-r", "rules.yaml", "-g", "pricing", "-g", "fraud", "--merge", "combined", "-f", "yaml"]).unwrap();
+        assert!(matches!(cli.command, Command::Eval { ref evaluation, merge: Some(Merge::Combined) }
+            if evaluation.groups == ["pricing", "fraud"] && evaluation.input.is_none()));
+
+        assert!(Cli::try_parse_from(["logicloom", "eval", "-r", "rules.yaml"]).is_err());
+        assert!(Cli::try_parse_from(["logicloom", "validate"]).is_err());
+        assert!(matches!(Cli::try_parse_from(["logicloom", "functions", "--kind", "list"]).unwrap().command,
+            Command::Functions { kind: Some(Kind::List), .. }));
+    }
+
+    #[test]
+    fn validate_files_together() {
+        let dir = std::env::temp_dir().join(format!("logicloom-validate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, source: &str| {
+            let path = dir.join(name);
+            fs::write(&path, source).unwrap();
+            path
+        };
+        let pricing = file("pricing.yaml", "groups: {pricing: {hit_policy: First, rules: [{name: loyal, conditions: []}]}}");
+        let shipping = file("shipping.yaml", "groups: {shipping: {rules: [{name: loyal, conditions: []}]}}");
+        let collect = file("collect.yaml", "groups: {pricing: {hit_policy: Collect, rules: [{name: bulk, conditions: []}]}}");
+
+        let problems = validate(&[pricing.clone(), shipping.clone(), collect.clone()]);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(problems[0].is_empty());
+        assert_eq!(problems[1], [format!(
+            "{}: rule `loyal`: groups.shipping.rules[0].name: rule name `loyal` is also used in group `pricing`",
+            shipping.display(),
+        )]);
+        assert_eq!(problems[2], [format!(
+            "{}: groups.pricing.hit_policy: hit policy `Collect` conflicts with `First`, set for the group before",
+            collect.display(),
+        )]);
+    }
 }
@@ -1,63 +1,251 @@
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use std::collections::HashMap;
+use std::fmt;
 
+use crate::error::{LogicLoomError, Result};
+use crate::function::numeric::{numbers_equal, DEFAULT_EPSILON};
 
+/// What happens when two rules write the same output path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeStrategy {
+    /// The later rule replaces the value.
+    LastWins,
+    /// The earlier rule keeps its value.
+    FirstWins,
+    /// Two different values fail the evaluation.
+    Error,
+    /// Values are appended to an existing array; other values fall back to `LastWins`.
+    #[default]
+    ArrayAppend,
+    /// Like `ArrayAppend`, but values already in the array are skipped.
+    ArrayUnion,
+    /// Numbers are added up; other values fall back to `LastWins`.
+    Sum,
+    /// The larger number is kept; other values fall back to `LastWins`.
+    Max,
+    /// The smaller number is kept; other values fall back to `LastWins`.
+    Min,
+    /// Objects are merged key by key, each key with the strategy of its own path; other values fall back to `LastWins`.
+    DeepMerge,
+}
 
-fn build_output(output_results: Vec<HashMap<String, Value>>) -> Result<Map<String, Value>, Box<dyn std::error::Error>> {
-    let mut output = Map::new();
+/// The merge strategy of the engine and of single output paths, e.g. `discount.percent: Max`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputMerge {
+    /// The strategy of paths without one of their own, `ArrayAppend` when not set. `None` lets a rule file that only
+    /// sets `paths` keep the default set before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<MergeStrategy>,
+    #[serde(default)]
+    pub paths: HashMap<String, MergeStrategy>,
+}
 
-    for result in output_results {
-        for (k, v) in result {
-            set_output_value(&mut output, &k, v);
-        }
+impl OutputMerge {
+    pub fn new(default: MergeStrategy) -> Self {
+        OutputMerge { default: Some(default), paths: HashMap::new() }
+    }
+
+    pub fn strategy(&self, path: &str) -> MergeStrategy {
+        self.paths.get(path).copied().unwrap_or(self.default_strategy())
     }
 
-    Ok(output)
+    /// The strategy of paths without one of their own.
+    pub fn default_strategy(&self) -> MergeStrategy {
+        self.default.unwrap_or_default()
+    }
+
+    /// Adds the settings of `other`: its default replaces this one only when it sets one.
+    pub fn extend(&mut self, other: OutputMerge) {
+        self.default = other.default.or(self.default);
+        self.paths.extend(other.paths);
+    }
+}
+
+/// Two rules wrote different values to the same path.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MergeConflict {
+    pub path: String,
+    /// The rule that wrote `incoming`.
+    pub rule: String,
+    pub strategy: MergeStrategy,
+    pub existing: Value,
+    pub incoming: Value,
 }
 
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rule [{}] writes [{}] to output [{}] which already holds [{}] ({:?})",
+            self.rule, self.incoming, self.path, self.existing, self.strategy)
+    }
+}
 
-fn set_output_value(m: &mut Map<String, Value>, path: &str, val: Value) {
-    if let Some(dot_index) = path.find('.') {
-        // If the path contains a dot, split it into the current key and the remaining path.
-        let (current_key, remaining_path) = path.split_at(dot_index);
-        let current_key = current_key.to_owned();
-        let remaining_path = &remaining_path[1..]; // Skip the dot.
+/// The merged outputs and the conflicts that were resolved on the way.
+#[derive(Debug, Default)]
+pub struct MergedOutput {
+    pub output: Map<String, Value>,
+    pub conflicts: Vec<MergeConflict>,
+}
 
-        // Ensure there is a sub-map to recurse into.
-        if !m.contains_key(&current_key) {
-            m.insert(current_key.clone(), Value::Object(Map::new()));
+/// Merges the outputs of the rules that matched, in rule order, into one nested map.
+/// Keys are dot separated paths; the keys of one rule are applied in sorted order so the result does not depend on map order.
+pub(crate) fn build_output<'a>(output_results: impl IntoIterator<Item = (&'a str, &'a HashMap<String, Value>)>, merge: &OutputMerge) -> Result<MergedOutput> {
+    let mut merged = MergedOutput::default();
+
+    for (rule, result) in output_results {
+        let mut merger = Merger { merge, rule, conflicts: &mut merged.conflicts };
+        let mut keys: Vec<&String> = result.keys().collect();
+        keys.sort_unstable();
+        for k in keys {
+            merger.set_output_value(&mut merged.output, "", k, result[k].clone())?;
         }
+    }
+
+    Ok(merged)
+}
+
+struct Merger<'a> {
+    merge: &'a OutputMerge,
+    rule: &'a str,
+    conflicts: &'a mut Vec<MergeConflict>,
+}
+
+impl Merger<'_> {
+    // `prefix` is the path of `m` in the output, `path` the path below it.
+    fn set_output_value(&mut self, m: &mut Map<String, Value>, prefix: &str, path: &str, val: Value) -> Result<()> {
+        let (current_key, remaining_path) = match path.split_once('.') {
+            Some((current_key, remaining_path)) => (current_key, Some(remaining_path)),
+            None => (path, None),
+        };
+        let full_path = join(prefix, current_key);
 
-        // Recurse into the sub-map.
-        if let Some(Value::Object(sub_map)) = m.get_mut(&current_key) {
-            set_output_value(sub_map, remaining_path, val);
+        match (m.get_mut(current_key), remaining_path) {
+            (None, None) => {
+                m.insert(current_key.to_owned(), val);
+            }
+            (None, Some(remaining_path)) => {
+                let mut sub_map = Map::new();
+                self.set_output_value(&mut sub_map, &full_path, remaining_path, val)?;
+                m.insert(current_key.to_owned(), Value::Object(sub_map));
+            }
+            (Some(Value::Object(sub_map)), Some(remaining_path)) => {
+                self.set_output_value(sub_map, &full_path, remaining_path, val)?;
+            }
+            // The path runs through a value that is not an object, so the object it would create conflicts with that value.
+            (Some(existing), Some(remaining_path)) => {
+                self.merge_value(&full_path, existing, nest(remaining_path, val), false)?;
+            }
+            (Some(existing), None) => {
+                self.merge_value(&full_path, existing, val, false)?;
+            }
         }
-    } else {
-        // If the path does not contain a dot, set the value directly.
-        match m.get_mut(path) {
-            Some(existing_val) if existing_val.is_array() => {
-                // If the existing value is an array, push the new value into it.
-                //existing_val.as_array_mut().unwrap().push(val);
-                if let Value::Array(new_arr) = val {
-                    existing_val.as_array_mut().unwrap().extend(new_arr);
-                } else {
-                    // If the new value is not an array, just push it into the existing array.
-                    existing_val.as_array_mut().unwrap().push(val);
+        Ok(())
+    }
+
+    // `in_deep_merge` is set for the keys of objects being deep merged, whose nested objects are deep merged as well
+    // unless their path has a strategy of its own.
+    fn merge_value(&mut self, path: &str, existing: &mut Value, incoming: Value, in_deep_merge: bool) -> Result<()> {
+        let strategy = match self.merge.paths.get(path) {
+            Some(strategy) => *strategy,
+            None if in_deep_merge && existing.is_object() && incoming.is_object() => MergeStrategy::DeepMerge,
+            None => self.merge.default_strategy(),
+        };
+
+        match (strategy, &mut *existing, incoming) {
+            (MergeStrategy::FirstWins, existing, incoming) => {
+                if *existing != incoming {
+                    self.conflict(path, strategy, existing, incoming);
                 }
             }
-            _ => {
-                // Otherwise, insert or overwrite the value at the path.
-                m.insert(path.to_owned(), val);
+            (MergeStrategy::Error, existing, incoming) => {
+                if *existing != incoming {
+                    return Err(LogicLoomError::OutputConflict(Box::new(self.new_conflict(path, strategy, existing, incoming))));
+                }
+            }
+            (MergeStrategy::ArrayAppend, Value::Array(array), incoming) => match incoming {
+                Value::Array(elements) => array.extend(elements),
+                element => array.push(element),
+            },
+            (MergeStrategy::ArrayUnion, Value::Array(array), incoming) => {
+                let elements = match incoming {
+                    Value::Array(elements) => elements,
+                    element => vec![element],
+                };
+                for element in elements {
+                    if !array.iter().any(|existing| numbers_equal(existing, &element, DEFAULT_EPSILON)) {
+                        array.push(element);
+                    }
+                }
+            }
+            (MergeStrategy::Sum | MergeStrategy::Max | MergeStrategy::Min, Value::Number(a), Value::Number(b)) => {
+                *existing = combine_numbers(strategy, a, &b);
+            }
+            (MergeStrategy::DeepMerge, Value::Object(object), Value::Object(incoming)) => {
+                for (key, value) in incoming {
+                    let child_path = join(path, &key);
+                    match object.get_mut(&key) {
+                        Some(child) => self.merge_value(&child_path, child, value, true)?,
+                        None => {
+                            object.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (_, existing, incoming) => {
+                if *existing != incoming {
+                    self.conflict(path, strategy, existing, incoming.clone());
+                    *existing = incoming;
+                }
             }
         }
+        Ok(())
+    }
+
+    fn new_conflict(&self, path: &str, strategy: MergeStrategy, existing: &Value, incoming: Value) -> MergeConflict {
+        MergeConflict { path: path.to_owned(), rule: self.rule.to_owned(), strategy, existing: existing.clone(), incoming }
     }
+
+    fn conflict(&mut self, path: &str, strategy: MergeStrategy, existing: &Value, incoming: Value) {
+        let conflict = self.new_conflict(path, strategy, existing, incoming);
+        self.conflicts.push(conflict);
+    }
+}
+
+fn combine_numbers(strategy: MergeStrategy, a: &serde_json::Number, b: &serde_json::Number) -> Value {
+    let (x, y) = (a.as_f64().unwrap_or(f64::NAN), b.as_f64().unwrap_or(f64::NAN));
+    match strategy {
+        MergeStrategy::Max if y > x => Value::Number(b.clone()),
+        MergeStrategy::Min if y < x => Value::Number(b.clone()),
+        MergeStrategy::Sum => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) if a.checked_add(b).is_some() => Value::from(a + b),
+            _ => serde_json::Number::from_f64(x + y).map_or(Value::Null, Value::Number),
+        },
+        _ => Value::Number(a.clone()),
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+// Turns `a.b` and a value into `{"a": {"b": value}}`.
+fn nest(path: &str, val: Value) -> Value {
+    path.rsplit('.').fold(val, |val, key| {
+        let mut m = Map::new();
+        m.insert(key.to_owned(), val);
+        Value::Object(m)
+    })
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde::{Serialize, Deserialize};
     use std::collections::HashMap;
     use serde_yaml;
     #[test]
@@ -103,7 +291,7 @@ fn test_build_output() {
         Value::Number(serde_json::Number::from(44)),
     ]));
 
-    let results = vec![map1, map2, map3, map4, map5, map6, map7, map8];
+    let results = [map1, map2, map3, map4, map5, map6, map7, map8];
     
 
     // Define the expected YAML output as a string.
@@ -131,7 +319,8 @@ numbers:
 "#;
 
     // Call the build_output function to process the results.
-    let output = build_output(results).expect("expected no error when building output");
+    let output = build_output(results.iter().map(|result| ("", result)), &OutputMerge::default())
+        .expect("expected no error when building output").output;
 
     println!("{:?}", output);
 
@@ -146,4 +335,102 @@ numbers:
     // Compare the actual output with the expected output.
     assert_eq!(expected_yaml_output, yaml_output, "expected yaml output to be as the defined one");
 }
-}
\ No newline at end of file
+
+    #[test]
+    fn build_output_applies_keys_of_a_rule_in_order() {
+        let mut rule = HashMap::new();
+        rule.insert("discount.percent".to_string(), Value::from(10));
+        rule.insert("discount".to_string(), serde_json::json!({"code": "SPRING"}));
+
+        let output = build_output([("spring", &rule)], &OutputMerge::default()).unwrap().output;
+
+        assert_eq!(Value::Object(output), serde_json::json!({"discount": {"code": "SPRING", "percent": 10}}));
+    }
+
+    fn merge(merge: &OutputMerge, outputs: &[(&str, Value)]) -> Result<MergedOutput> {
+        let outputs: Vec<(&str, HashMap<String, Value>)> = outputs.iter()
+            .map(|(rule, output)| (*rule, serde_json::from_value(output.clone()).unwrap()))
+            .collect();
+        build_output(outputs.iter().map(|(rule, output)| (*rule, output)), merge)
+    }
+
+    #[test]
+    fn merge_strategies() {
+        use serde_json::json;
+        let outputs = [
+            ("a", json!({"price": 10, "tags": ["x", "y"], "label": "first"})),
+            ("b", json!({"price": 2.5, "tags": ["y", "z"], "label": "second"})),
+        ];
+        let cases = [
+            (MergeStrategy::LastWins, json!({"price": 2.5, "tags": ["y", "z"], "label": "second"})),
+            (MergeStrategy::FirstWins, json!({"price": 10, "tags": ["x", "y"], "label": "first"})),
+            (MergeStrategy::ArrayAppend, json!({"price": 2.5, "tags": ["x", "y", "y", "z"], "label": "second"})),
+            (MergeStrategy::ArrayUnion, json!({"price": 2.5, "tags": ["x", "y", "z"], "label": "second"})),
+            (MergeStrategy::Sum, json!({"price": 12.5, "tags": ["y", "z"], "label": "second"})),
+            (MergeStrategy::Max, json!({"price": 10, "tags": ["y", "z"], "label": "second"})),
+            (MergeStrategy::Min, json!({"price": 2.5, "tags": ["y", "z"], "label": "second"})),
+        ];
+
+        for (strategy, expected) in cases {
+            let merged = merge(&OutputMerge::new(strategy), &outputs).unwrap();
+            assert_eq!(Value::Object(merged.output), expected, "{:?}", strategy);
+        }
+
+        let err = merge(&OutputMerge::new(MergeStrategy::Error), &outputs).unwrap_err();
+        assert_eq!(err.code(), "output_conflict");
+        assert_eq!(err.to_string(), "rule [b] writes [\"second\"] to output [label] which already holds [\"first\"] (Error)");
+    }
+
+    #[test]
+    fn merge_strategy_per_path() {
+        use serde_json::json;
+        let mut settings = OutputMerge::new(MergeStrategy::FirstWins);
+        settings.paths.insert("discount.percent".to_owned(), MergeStrategy::Max);
+        settings.paths.insert("total".to_owned(), MergeStrategy::Sum);
+
+        let merged = merge(&settings, &[
+            ("a", json!({"discount.percent": 5, "discount.code": "A", "total": 1})),
+            ("b", json!({"discount.percent": 15, "discount.code": "B", "total": 2})),
+        ]).unwrap();
+
+        assert_eq!(Value::Object(merged.output), json!({"discount": {"percent": 15, "code": "A"}, "total": 3}));
+        assert_eq!(merged.conflicts, vec![MergeConflict {
+            path: "discount.code".to_owned(),
+            rule: "b".to_owned(),
+            strategy: MergeStrategy::FirstWins,
+            existing: json!("A"),
+            incoming: json!("B"),
+        }]);
+    }
+
+    #[test]
+    fn deep_merge_objects() {
+        use serde_json::json;
+        let mut settings = OutputMerge::new(MergeStrategy::DeepMerge);
+        settings.paths.insert("limits.daily".to_owned(), MergeStrategy::Min);
+
+        let merged = merge(&settings, &[
+            ("a", json!({"limits": {"daily": 500, "card": {"atm": true}}})),
+            ("b", json!({"limits": {"daily": 200, "card": {"online": false}, "note": "b"}})),
+        ]).unwrap();
+
+        assert_eq!(Value::Object(merged.output), json!({
+            "limits": {"daily": 200, "card": {"atm": true, "online": false}, "note": "b"},
+        }));
+        assert!(merged.conflicts.is_empty());
+    }
+
+    #[test]
+    fn scalar_and_object_at_the_same_path_conflict() {
+        use serde_json::json;
+        let merged = merge(&OutputMerge::default(), &[
+            ("a", json!({"discount": 5})),
+            ("b", json!({"discount.percent": 10})),
+        ]).unwrap();
+
+        assert_eq!(Value::Object(merged.output), json!({"discount": {"percent": 10}}));
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].path, "discount");
+        assert_eq!(merged.conflicts[0].incoming, json!({"percent": 10}));
+    }
+}
@@ -0,0 +1,209 @@
+use std::error::Error;
+use std::fmt;
+
+use serde_json::Value;
+
+/// One step of an input path: an object key or an array index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    /// The path is not well formed, e.g. `items[x]` or `a..b`.
+    Invalid { path: String, message: String },
+    /// Nothing exists at `missing`, the first prefix of `path` that could not be resolved.
+    NotFound { path: String, missing: String },
+    /// A list was expected at `path` but something else was found.
+    NotAList { path: String },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::Invalid { path, message } => write!(f, "invalid path [{}]: {}", path, message),
+            PathError::NotFound { path, missing } if path == missing => write!(f, "path not found: [{}]", path),
+            PathError::NotFound { path, missing } => write!(f, "path not found: [{}], nothing at [{}]", path, missing),
+            PathError::NotAList { path } => write!(f, "value at [{}] is not a list", path),
+        }
+    }
+}
+
+impl Error for PathError {}
+
+/// Splits `customer.address.country` or `items[0].sku` into its segments.
+pub fn parse(path: &str) -> Result<Vec<Segment<'_>>, PathError> {
+    let invalid = |message: &str| PathError::Invalid { path: path.to_owned(), message: message.to_owned() };
+
+    let mut segments = Vec::new();
+    for (i, part) in path.split('.').enumerate() {
+        let (key, mut indexes) = match part.find('[') {
+            Some(bracket) => part.split_at(bracket),
+            None => (part, ""),
+        };
+        // Only a path into a top level array, like `[0].sku`, may start without a key.
+        if key.is_empty() && (i > 0 || indexes.is_empty()) {
+            return Err(invalid("empty key"));
+        }
+        if !key.is_empty() {
+            segments.push(Segment::Key(key));
+        }
+        while !indexes.is_empty() {
+            let close = indexes.find(']').ok_or_else(|| invalid("missing `]`"))?;
+            let index = indexes[1..close].parse::<usize>().map_err(|_| invalid("array index is not a number"))?;
+            segments.push(Segment::Index(index));
+            indexes = &indexes[close + 1..];
+            if !indexes.is_empty() && !indexes.starts_with('[') {
+                return Err(invalid("unexpected characters after `]`"));
+            }
+        }
+    }
+    Ok(segments)
+}
+
+/// Resolves a path against a nested input document.
+pub fn resolve<'a>(input: &'a Value, path: &str) -> Result<&'a Value, PathError> {
+    let segments = parse(path)?;
+    let mut current = input;
+    for (i, segment) in segments.iter().enumerate() {
+        let next = match segment {
+            Segment::Key(key) => current.as_object().and_then(|object| object.get(*key)),
+            Segment::Index(index) => current.as_array().and_then(|array| array.get(*index)),
+        };
+        current = next.ok_or_else(|| PathError::NotFound {
+            path: path.to_owned(),
+            missing: to_path(&segments[..=i]),
+        })?;
+    }
+    Ok(current)
+}
+
+/// Resolves a path to a list of values. A key applied to a list is looked up in every element,
+/// so `order.items.price` gives the price of each item, and lists found at the end are flattened.
+pub fn resolve_list<'a>(input: &'a Value, path: &str) -> Result<Vec<&'a Value>, PathError> {
+    let segments = parse(path)?;
+    let mut current = vec![input];
+    let mut projected = false;
+    for (i, segment) in segments.iter().enumerate() {
+        let mut next = Vec::with_capacity(current.len());
+        for value in current {
+            let found = match (segment, value) {
+                (Segment::Key(key), Value::Array(elements)) => {
+                    projected = true;
+                    elements.iter().map(|element| element.get(*key)).collect()
+                }
+                (Segment::Key(key), value) => vec![value.as_object().and_then(|object| object.get(*key))],
+                (Segment::Index(index), value) => vec![value.as_array().and_then(|array| array.get(*index))],
+            };
+            for value in found {
+                next.push(value.ok_or_else(|| PathError::NotFound {
+                    path: path.to_owned(),
+                    missing: to_path(&segments[..=i]),
+                })?);
+            }
+        }
+        current = next;
+    }
+
+    if !projected && !current.iter().all(|value| value.is_array()) {
+        return Err(PathError::NotAList { path: path.to_owned() });
+    }
+    let mut list = Vec::new();
+    for value in current {
+        match value {
+            Value::Array(elements) => list.extend(elements),
+            value => list.push(value),
+        }
+    }
+    Ok(list)
+}
+
+fn to_path(segments: &[Segment<'_>]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Key(key) if path.is_empty() => path.push_str(key),
+            Segment::Key(key) => {
+                path.push('.');
+                path.push_str(key);
+            }
+            Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_keys_and_indexes() {
+        assert_eq!(parse("customer.address.country").unwrap(), vec![
+            Segment::Key("customer"), Segment::Key("address"), Segment::Key("country"),
+        ]);
+        assert_eq!(parse("items[0].sku").unwrap(), vec![
+            Segment::Key("items"), Segment::Index(0), Segment::Key("sku"),
+        ]);
+        assert_eq!(parse("matrix[1][2]").unwrap(), vec![
+            Segment::Key("matrix"), Segment::Index(1), Segment::Index(2),
+        ]);
+    }
+
+    #[test]
+    fn parse_invalid_paths() {
+        assert!(parse("").is_err());
+        assert!(parse("a..b").is_err());
+        assert!(parse("items[x]").is_err());
+        assert!(parse("items[0").is_err());
+        assert!(parse("items[0]sku").is_err());
+        assert!(parse("items.[0]").is_err());
+        assert_eq!(parse("[0].sku").unwrap(), vec![Segment::Index(0), Segment::Key("sku")]);
+    }
+
+    #[test]
+    fn resolve_nested_values() {
+        let input = json!({
+            "customer": {"address": {"country": "IN"}},
+            "items": [{"sku": "A-1"}, {"sku": "B-2"}],
+        });
+
+        assert_eq!(resolve(&input, "customer.address.country").unwrap(), &json!("IN"));
+        assert_eq!(resolve(&input, "items[1].sku").unwrap(), &json!("B-2"));
+        assert_eq!(resolve(&input, "items").unwrap().as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn resolve_missing_path() {
+        let input = json!({"customer": {"name": "Ankit"}, "items": []});
+
+        let err = resolve(&input, "customer.address.country").unwrap_err();
+        assert_eq!(err, PathError::NotFound {
+            path: "customer.address.country".to_owned(),
+            missing: "customer.address".to_owned(),
+        });
+        assert_eq!(err.to_string(), "path not found: [customer.address.country], nothing at [customer.address]");
+
+        let err = resolve(&input, "items[0].sku").unwrap_err();
+        assert_eq!(err.to_string(), "path not found: [items[0].sku], nothing at [items[0]]");
+    }
+
+    #[test]
+    fn resolve_list_projects_over_elements() {
+        let input = json!({
+            "order": {"items": [{"price": 120, "tags": ["a", "b"]}, {"price": 80, "tags": ["c"]}]},
+            "total": 200,
+        });
+
+        assert_eq!(resolve_list(&input, "order.items").unwrap().len(), 2);
+        assert_eq!(resolve_list(&input, "order.items.price").unwrap(), vec![&json!(120), &json!(80)]);
+        assert_eq!(resolve_list(&input, "order.items.tags").unwrap(), vec![&json!("a"), &json!("b"), &json!("c")]);
+        assert_eq!(resolve_list(&input, "order.items[1].tags").unwrap(), vec![&json!("c")]);
+
+        assert_eq!(resolve_list(&input, "total").unwrap_err(), PathError::NotAList { path: "total".to_owned() });
+        assert!(matches!(resolve_list(&input, "order.items.sku").unwrap_err(), PathError::NotFound { .. }));
+    }
+}
@@ -1,22 +1,791 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
-use crate::core::Rule;
-use crate::condition::ConditionType;
-use crate::function::Function;
-use crate::function::aggregator::AggregateFunction;
-use crate::function::list_function::ListFunctionConstraint;
+use std::sync::Arc;
 
-struct Engine {
-    rules: HashMap<String, Vec<Rule>>,
-    condition_chains: HashMap<String, ConditionType>,
-    input_functions: HashMap<String, Function>,
-    aggregate_function: AggregateFunction,
-    input_constraints: ListFunctionConstraint,
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::core::{HitPolicy, Rule, RuleGroup};
+use crate::error::{LogicLoomError, Result};
+use crate::condition::{ConditionType, EvalContext};
+use crate::function::date::Clock;
+use crate::function::{FunctionOptions, FunctionRegistry};
+use crate::loader::{conflicting_hit_policy, LoadError, RuleFile};
+use crate::output::{build_output, MergeConflict, MergeStrategy, OutputMerge};
+use crate::template::render_output;
+use crate::trace::RuleTrace;
+use crate::validate::validate_added;
+
+use super::hit_policy;
+
+/// The result of evaluating a group.
+#[derive(Debug, Default, Serialize)]
+pub struct Evaluation {
+    pub output: Map<String, Value>,
+    /// Errors of rules whose `on_error` policy is `Collect`.
+    pub errors: Vec<LogicLoomError>,
+    /// Output paths written with different values by several rules, and how they were resolved.
+    pub conflicts: Vec<MergeConflict>,
+    /// How every rule was evaluated, only filled in by `Engine::explain`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<RuleTrace>>,
+}
+
+/// How `Engine::evaluate_groups` combines the outputs of several groups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GroupMerge {
+    /// The output holds one object per group, keyed by the group name.
+    #[default]
+    PerGroup,
+    /// The outputs of all groups are merged into one object, as if their rules were in one group.
+    Combined,
+}
+
+// What the rules of one group produced, after its hit policy was applied.
+#[derive(Default)]
+struct GroupRun<'a> {
+    outputs: Vec<(&'a str, Cow<'a, HashMap<String, Value>>)>,
+    errors: Vec<LogicLoomError>,
+    traces: Vec<RuleTrace>,
+}
+
+pub struct Engine {
+    rules: HashMap<String, RuleGroup>,
+    functions: Arc<FunctionRegistry>,
+    merge: OutputMerge,
 }
 
 impl Engine {
     pub fn new() -> Self {
         Engine {
-            
+            rules: HashMap::new(),
+            functions: Arc::new(FunctionRegistry::default()),
+            merge: OutputMerge::default(),
         }
     }
-}
\ No newline at end of file
+
+    /// An engine whose rules call `functions` instead of the default ones, e.g. `FunctionRegistry::with_options`.
+    /// Engines given the same `Arc` share the functions and their compiled patterns.
+    pub fn with_functions(functions: impl Into<Arc<FunctionRegistry>>) -> Self {
+        Engine { functions: functions.into(), ..Engine::new() }
+    }
+
+    /// An engine whose date functions take "now" from `clock`, e.g. a `FixedClock` in tests.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Engine::with_functions(FunctionRegistry::with_options(FunctionOptions { clock, ..FunctionOptions::default() }))
+    }
+
+    /// Adds a rule to a group. The rule is validated against the functions of the engine first, see
+    /// `validate::validate_file`, and its name must not be used by a rule of any group; a rule that fails is not added
+    /// and its problems are reported as `InvalidRules`. Its regular expressions are compiled on the way, so evaluations
+    /// only look them up.
+    pub fn add_rule(&mut self, group: &str, rule: Rule) -> Result<()> {
+        self.add_rules(group, [rule])
+    }
+
+    /// Adds rules to a group, none of them when one of them is rejected by `add_rule`.
+    pub fn add_rules(&mut self, group: &str, rules: impl IntoIterator<Item = Rule>) -> Result<()> {
+        let rules: Vec<Rule> = rules.into_iter().collect();
+        rejected(validate_added(&self.rules, [(group, rules.as_slice())], &self.functions))?;
+        self.rules.entry(group.to_owned()).or_default().rules.extend(rules);
+        Ok(())
+    }
+
+    /// Sets the hit policy of a group, creating the group if needed.
+    pub fn set_hit_policy(&mut self, group: &str, policy: HitPolicy) {
+        self.rules.entry(group.to_owned()).or_default().hit_policy = Some(policy);
+    }
+
+    /// Sets the strategy for output paths without a strategy of their own.
+    pub fn set_merge_strategy(&mut self, strategy: MergeStrategy) {
+        self.merge.default = Some(strategy);
+    }
+
+    pub fn set_path_merge_strategy(&mut self, path: &str, strategy: MergeStrategy) {
+        self.merge.paths.insert(path.to_owned(), strategy);
+    }
+
+    pub fn merge(&self) -> &OutputMerge {
+        &self.merge
+    }
+
+    /// Registers every group of a loaded rule file. A default strategy set in the `merge` settings of the file
+    /// replaces the one of the engine, and its path strategies are added. A group keeps its hit policy unless the file sets one, and a file setting
+    /// a different one than was set before is rejected. Nothing is registered when the file or a rule of the file is
+    /// rejected by `add_rule`.
+    pub fn add_rule_file(&mut self, file: RuleFile) -> Result<()> {
+        let added = file.groups.iter().map(|(group, rule_group)| (group.as_str(), rule_group.rules.as_slice()));
+        let mut diagnostics: Vec<LoadError> = file.groups.iter()
+            .filter_map(|(name, group)| conflicting_hit_policy(name, self.rules.get(name)?.hit_policy, group.hit_policy))
+            .collect();
+        diagnostics.extend(validate_added(&self.rules, added, &self.functions));
+        rejected(diagnostics)?;
+        if let Some(merge) = file.merge {
+            self.merge.extend(merge);
+        }
+        for (group, rule_group) in file.groups {
+            let own = self.rules.entry(group).or_default();
+            own.hit_policy = rule_group.hit_policy.or(own.hit_policy);
+            own.rules.extend(rule_group.rules);
+        }
+        Ok(())
+    }
+
+    /// The names of the registered groups, sorted.
+    pub fn groups(&self) -> Vec<&str> {
+        let mut groups: Vec<&str> = self.rules.keys().map(String::as_str).collect();
+        groups.sort_unstable();
+        groups
+    }
+
+    pub fn rules(&self, group: &str) -> Option<&[Rule]> {
+        self.rules.get(group).map(|group| group.rules.as_slice())
+    }
+
+    pub fn group(&self, group: &str) -> Option<&RuleGroup> {
+        self.rules.get(group)
+    }
+
+    /// The functions rules can call; `signatures()` lists them for tools that write rules.
+    pub fn functions(&self) -> &FunctionRegistry {
+        &self.functions
+    }
+
+    /// Checks the registered rules against the registered functions again, see `validate::validate_file`.
+    pub fn validate(&self) -> Vec<LoadError> {
+        let groups = self.groups().into_iter().map(|name| (name, self.rules[name].rules.as_slice()));
+        validate_added(&HashMap::new(), groups, &self.functions)
+    }
+
+    /// Applies every rule of `group` to `input` and merges the outputs of the rules that matched.
+    pub fn evaluate(&self, group: &str, input: &Value) -> Result<Map<String, Value>> {
+        self.evaluate_detailed(group, input).map(|evaluation| evaluation.output)
+    }
+
+    /// Like `evaluate`, but also returns the errors collected by the rules.
+    pub fn evaluate_detailed(&self, group: &str, input: &Value) -> Result<Evaluation> {
+        self.evaluate_group(group, input, false)
+    }
+
+    /// Like `evaluate_detailed`, but also traces every condition, function call and output of every rule.
+    pub fn explain(&self, group: &str, input: &Value) -> Result<Evaluation> {
+        self.evaluate_group(group, input, true)
+    }
+
+    /// Applies the rules of several groups to the same input. The groups are checked to exist before any rule is evaluated,
+    /// and a group listed twice is evaluated once.
+    pub fn evaluate_groups(&self, groups: &[&str], input: &Value, merge: GroupMerge) -> Result<Evaluation> {
+        if let Some(group) = groups.iter().find(|group| !self.rules.contains_key(**group)) {
+            return Err(LogicLoomError::UnknownGroup { group: group.to_string() });
+        }
+
+        let mut evaluated = Vec::with_capacity(groups.len());
+        let mut evaluation = Evaluation::default();
+        let mut combined = Vec::new();
+        for group in groups {
+            if evaluated.contains(group) {
+                continue;
+            }
+            evaluated.push(*group);
+            let run = self.run_group(group, input, false)?;
+            evaluation.errors.extend(run.errors);
+            match merge {
+                GroupMerge::PerGroup => {
+                    let merged = build_output(run.outputs.iter().map(|(rule, output)| (*rule, output.as_ref())), &self.merge)?;
+                    evaluation.output.insert(group.to_string(), Value::Object(merged.output));
+                    evaluation.conflicts.extend(merged.conflicts);
+                }
+                GroupMerge::Combined => combined.extend(run.outputs),
+            }
+        }
+        if merge == GroupMerge::Combined {
+            let merged = build_output(combined.iter().map(|(rule, output)| (*rule, output.as_ref())), &self.merge)?;
+            evaluation.output = merged.output;
+            evaluation.conflicts = merged.conflicts;
+        }
+        Ok(evaluation)
+    }
+
+    fn evaluate_group(&self, group: &str, input: &Value, trace: bool) -> Result<Evaluation> {
+        let run = self.run_group(group, input, trace)?;
+        let merged = build_output(run.outputs.iter().map(|(rule, output)| (*rule, output.as_ref())), &self.merge)?;
+        Ok(Evaluation {
+            output: merged.output,
+            errors: run.errors,
+            conflicts: merged.conflicts,
+            trace: trace.then_some(run.traces),
+        })
+    }
+
+    fn run_group<'a>(&'a self, group: &'a str, input: &Value, trace: bool) -> Result<GroupRun<'a>> {
+        let rule_group = self.rules.get(group)
+            .ok_or_else(|| LogicLoomError::UnknownGroup { group: group.to_owned() })?;
+
+        let mut run = GroupRun::default();
+        let mut matches = Vec::new();
+        for rule in &rule_group.rules {
+            let mut context = EvalContext::new(&self.functions, rule.on_error);
+            if trace {
+                context = context.traced();
+            }
+            let matched = ConditionType::new(rule.chain)
+                .evaluate(input, &rule.conditions, &mut context)
+                .map_err(|e| e.in_rule(&rule.name))?;
+            run.errors.extend(context.errors.into_iter().map(|e| e.in_rule(&rule.name)));
+            if let Some(conditions) = context.trace {
+                run.traces.push(RuleTrace { rule: rule.name.clone(), chain: rule.chain, conditions, matched, output: HashMap::new() });
+            }
+            if matched {
+                matches.push((rule, context.captures));
+                if rule_group.hit_policy() == HitPolicy::First {
+                    break;
+                }
+            }
+        }
+
+        // Only the outputs the hit policy can use are rendered, so a rule it passes over can not fail the group.
+        let matches = hit_policy::candidates(group, rule_group.hit_policy(), matches)?
+            .into_iter()
+            .map(|(rule, captures)| Ok((rule, render_output(&rule.output, input, &captures).map_err(|e| e.in_rule(&rule.name))?)))
+            .collect::<Result<Vec<_>>>()?;
+        for (rule, rule_trace) in rule_group.rules.iter().zip(&mut run.traces) {
+            if let Some((_, output)) = matches.iter().find(|(matched, _)| std::ptr::eq(*matched, rule)) {
+                rule_trace.output = output.clone().into_owned();
+            }
+        }
+
+        let hits = hit_policy::apply(group, rule_group.hit_policy(), matches)?;
+        // Only the rules selected by the hit policy contributed their output.
+        for (rule, rule_trace) in rule_group.rules.iter().zip(&mut run.traces) {
+            if !hits.selected.iter().any(|selected| std::ptr::eq(*selected, rule)) {
+                rule_trace.output.clear();
+            }
+        }
+        run.outputs = hits.outputs;
+        Ok(run)
+    }
+}
+
+fn rejected(diagnostics: Vec<LoadError>) -> Result<()> {
+    match diagnostics.is_empty() {
+        true => Ok(()),
+        false => Err(LogicLoomError::InvalidRules(diagnostics)),
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::date::FixedClock;
+    use crate::condition::LogicalOperator;
+    use crate::loader::{load_str, Format};
+    use serde_json::json;
+
+    const RULES: &str = r#"
+groups:
+  discounts:
+    rules:
+      - name: loyal
+        conditions:
+          - single:
+              input_path: years
+              functions:
+                - function: GreaterEq
+                  args: [3]
+        output:
+          discount.loyalty: 5
+          tags: [loyal]
+      - name: big_order
+        chain: OR
+        conditions:
+          - single:
+              input_path: total
+              functions:
+                - function: Greater
+                  args: [1000]
+          - single:
+              input_path: tier
+              functions:
+                - function: EqualIgnoreCase
+                  args: [gold]
+        output:
+          discount.volume: 10
+          tags: [volume]
+"#;
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str(RULES, Format::Yaml, engine.functions()).unwrap()).unwrap();
+        engine
+    }
+
+    #[test]
+    fn evaluate_merges_matching_outputs() {
+        let input = json!({"years": 4, "total": 200.5, "tier": "GOLD"});
+        let output = engine().evaluate("discounts", &input).unwrap();
+
+        assert_eq!(Value::Object(output), json!({
+            "discount": {"loyalty": 5, "volume": 10},
+            "tags": ["loyal", "volume"],
+        }));
+    }
+
+    #[test]
+    fn evaluate_skips_rules_that_do_not_match() {
+        let input = json!({"years": 1, "total": "200", "tier": "silver"});
+        let output = engine().evaluate("discounts", &input).unwrap();
+
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn evaluate_collects_errors() {
+        let rules = r#"
+groups:
+  discounts:
+    rules:
+      - name: referral
+        chain: OR
+        on_error: Collect
+        conditions:
+          - single:
+              input_path: referrer.code
+              functions:
+                - function: NonEmpty
+          - single:
+              input_path: years
+              functions:
+                - function: GreaterEq
+                  args: [3]
+        output:
+          discount.referral: 2
+"#;
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str(rules, Format::Yaml, engine.functions()).unwrap()).unwrap();
+
+        let evaluation = engine.evaluate_detailed("discounts", &json!({"years": 4})).unwrap();
+        assert_eq!(Value::Object(evaluation.output), json!({"discount": {"referral": 2}}));
+        assert_eq!(evaluation.errors.len(), 1);
+        assert_eq!(evaluation.errors[0].to_string(), "rule [referral]: path [referrer.code]: path not found, nothing at [referrer]");
+
+        let evaluation = engine.evaluate_detailed("discounts", &json!({"years": 4, "referrer": {"code": "X1"}})).unwrap();
+        assert!(evaluation.errors.is_empty());
+    }
+
+    #[test]
+    fn explain_traces_every_rule() {
+        let input = json!({"years": 4, "total": 200.5, "tier": "GOLD"});
+        let evaluation = engine().explain("discounts", &input).unwrap();
+        let trace = evaluation.trace.unwrap();
+
+        assert_eq!(trace.len(), 2);
+        assert!(trace[0].matched && trace[1].matched);
+        assert_eq!(trace[0].output["discount.loyalty"], json!(5));
+        assert_eq!(trace[1].chain, LogicalOperator::OR);
+        assert_eq!(trace[1].conditions.len(), 2);
+        assert_eq!(trace[1].conditions[1].value, Some(json!("GOLD")));
+        assert_eq!(serde_json::to_value(&trace[1].conditions[1].functions).unwrap(), json!([
+            {"function": "EqualIgnoreCase", "args": ["gold"], "result": true},
+        ]));
+
+        assert!(engine().evaluate_detailed("discounts", &input).unwrap().trace.is_none());
+    }
+
+    #[test]
+    fn evaluate_unknown_group() {
+        let err = engine().evaluate("pricing", &json!({})).unwrap_err();
+        assert!(matches!(err, LogicLoomError::UnknownGroup { group } if group == "pricing"));
+
+        let err = engine().evaluate_groups(&["discounts", "pricing"], &json!({}), GroupMerge::PerGroup).unwrap_err();
+        assert!(matches!(err, LogicLoomError::UnknownGroup { group } if group == "pricing"));
+    }
+
+    #[test]
+    fn evaluate_reports_merge_conflicts() {
+        let rules = r#"
+merge:
+  default: LastWins
+  paths:
+    discount.percent: Max
+groups:
+  discounts:
+    rules:
+      - name: base
+        conditions: []
+        output:
+          discount.percent: 5
+          discount.label: base
+      - name: seasonal
+        conditions: []
+        output:
+          discount.percent: 15
+          discount.label: seasonal
+"#;
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str(rules, Format::Yaml, engine.functions()).unwrap()).unwrap();
+
+        let evaluation = engine.evaluate_detailed("discounts", &json!({})).unwrap();
+        assert_eq!(Value::Object(evaluation.output), json!({"discount": {"percent": 15, "label": "seasonal"}}));
+        assert_eq!(evaluation.conflicts.len(), 1);
+        assert_eq!(evaluation.conflicts[0].rule, "seasonal");
+        assert_eq!(evaluation.conflicts[0].path, "discount.label");
+
+        engine.set_path_merge_strategy("discount.label", MergeStrategy::Error);
+        let err = engine.evaluate("discounts", &json!({})).unwrap_err();
+        assert_eq!(err.code(), "output_conflict");
+    }
+
+    #[test]
+    fn rule_files_keep_the_default_merge_strategy() {
+        let first = r#"
+merge:
+  default: LastWins
+groups:
+  discounts:
+    rules:
+      - name: base
+        conditions: []
+        output:
+          discount.label: base
+          tags: [base]
+"#;
+        let second = r#"
+merge:
+  paths:
+    tags: ArrayUnion
+groups:
+  discounts:
+    rules:
+      - name: seasonal
+        conditions: []
+        output:
+          discount.label: seasonal
+          tags: [base, seasonal]
+"#;
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str(first, Format::Yaml, engine.functions()).unwrap()).unwrap();
+        engine.add_rule_file(load_str(second, Format::Yaml, engine.functions()).unwrap()).unwrap();
+
+        let output = engine.evaluate("discounts", &json!({})).unwrap();
+        assert_eq!(Value::Object(output), json!({"discount": {"label": "seasonal"}, "tags": ["base", "seasonal"]}));
+    }
+
+    #[test]
+    fn evaluate_with_hit_policies() {
+        let rules = r#"
+groups:
+  tariff:
+    hit_policy: First
+    rules:
+      - name: night
+        conditions:
+          - single: {input_path: hour, functions: [{function: GreaterEq, args: [22]}]}
+        output:
+          rate: 0.1
+      - name: weekend
+        priority: 5
+        conditions:
+          - single: {input_path: weekend, functions: [{function: Equal, args: [true]}]}
+        output:
+          rate: 0.15
+      - name: base
+        conditions: []
+        output:
+          rate: 0.2
+"#;
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str(rules, Format::Yaml, engine.functions()).unwrap()).unwrap();
+        let input = json!({"hour": 23, "weekend": true});
+
+        assert_eq!(engine.evaluate("tariff", &input).unwrap()["rate"], json!(0.1));
+        let trace = engine.explain("tariff", &input).unwrap().trace.unwrap();
+        assert_eq!(trace.len(), 1);
+
+        engine.set_hit_policy("tariff", HitPolicy::Priority);
+        assert_eq!(engine.evaluate("tariff", &input).unwrap()["rate"], json!(0.15));
+        let trace = engine.explain("tariff", &input).unwrap().trace.unwrap();
+        assert_eq!(trace.iter().map(|rule| rule.matched).collect::<Vec<_>>(), vec![true, true, true]);
+        assert_eq!(trace.iter().map(|rule| rule.output.len()).collect::<Vec<_>>(), vec![0, 1, 0]);
+
+        engine.set_hit_policy("tariff", HitPolicy::CollectSum);
+        assert_eq!(engine.evaluate("tariff", &json!({"hour": 10, "weekend": true})).unwrap()["rate"], json!(0.35));
+
+        engine.set_hit_policy("tariff", HitPolicy::Unique);
+        assert_eq!(engine.evaluate("tariff", &input).unwrap_err().code(), "hit_policy");
+        assert_eq!(engine.evaluate("tariff", &json!({"hour": 10, "weekend": false})).unwrap()["rate"], json!(0.2));
+    }
+
+    #[test]
+    fn render_only_the_outputs_the_hit_policy_uses() {
+        let rules = r#"
+groups:
+  tariff:
+    hit_policy: Priority
+    rules:
+      - name: fallback
+        conditions: []
+        output:
+          rate: "{{tariff.default_rate}}"
+      - name: night
+        priority: 10
+        conditions:
+          - single: {input_path: hour, functions: [{function: GreaterEq, args: [22]}]}
+        output:
+          rate: 0.1
+"#;
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str(rules, Format::Yaml, engine.functions()).unwrap()).unwrap();
+
+        assert_eq!(engine.evaluate("tariff", &json!({"hour": 23})).unwrap()["rate"], json!(0.1));
+        let trace = engine.explain("tariff", &json!({"hour": 23})).unwrap().trace.unwrap();
+        assert_eq!(trace.iter().map(|rule| rule.output.len()).collect::<Vec<_>>(), vec![0, 1]);
+        // The fallback wins without the night rule, and its template fails.
+        assert_eq!(engine.evaluate("tariff", &json!({"hour": 10})).unwrap_err().location().unwrap().rule.as_deref(), Some("fallback"));
+    }
+
+    #[test]
+    fn evaluate_renders_output_templates() {
+        let rules = r#"
+groups:
+  greeting:
+    rules:
+      - name: welcome
+        conditions: []
+        output:
+          message: "Hello {{customer.name}}"
+          cashback: "{{order.total * 0.1}}"
+          customer: "{{customer}}"
+"#;
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str(rules, Format::Yaml, engine.functions()).unwrap()).unwrap();
+
+        let input = json!({"customer": {"name": "Ankit"}, "order": {"total": 250}});
+        assert_eq!(Value::Object(engine.evaluate("greeting", &input).unwrap()), json!({
+            "message": "Hello Ankit",
+            "cashback": 25.0,
+            "customer": {"name": "Ankit"},
+        }));
+        assert_eq!(engine.explain("greeting", &input).unwrap().trace.unwrap()[0].output["cashback"], json!(25.0));
+
+        let err = engine.evaluate("greeting", &json!({"customer": {"name": "Ankit"}})).unwrap_err();
+        assert_eq!(err.to_string(), "rule [welcome]: path [order.total]: path not found, nothing at [order]");
+    }
+
+    #[test]
+    fn evaluate_renders_regex_captures() {
+        let rules = r#"
+groups:
+  sku:
+    rules:
+      - name: sized_sku
+        conditions:
+          - single: {input_path: sku, functions: [{function: Matches, args: ['^(?P<family>[A-Z]+)-(?P<id>\d+)(?:-(XS|S|M|L|XL))?$']}]}
+          - single: {input_path: comment, functions: [{function: NotMatches, args: ['(?i)<script']}]}
+        output:
+          family: "{{$family}}"
+          id: "{{$id}}"
+          size: "{{$3}}"
+          label: "{{$family}} #{{$id}}"
+"#;
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str(rules, Format::Yaml, engine.functions()).unwrap()).unwrap();
+        // Both patterns were compiled when the rules were added.
+        assert_eq!(engine.functions().patterns.len(), 2);
+
+        let output = engine.evaluate("sku", &json!({"sku": "TEE-42-XL", "comment": "nice"})).unwrap();
+        assert_eq!(Value::Object(output), json!({"family": "TEE", "id": "42", "size": "XL", "label": "TEE #42"}));
+
+        let output = engine.evaluate("sku", &json!({"sku": "TEE-42", "comment": "nice"})).unwrap();
+        assert_eq!(output["size"], json!(null));
+        assert!(engine.evaluate("sku", &json!({"sku": "tee-42", "comment": "nice"})).unwrap().is_empty());
+        assert!(engine.evaluate("sku", &json!({"sku": "TEE-42", "comment": "<SCRIPT>"})).unwrap().is_empty());
+        assert_eq!(engine.functions().patterns.len(), 2);
+    }
+
+    #[test]
+    fn captures_only_come_from_conditions_that_held() {
+        let rules = r#"
+groups:
+  sku:
+    hit_policy: Collect
+    rules:
+      - name: negated
+        conditions:
+          - or:
+              - not: {single: {input_path: sku, functions: [{function: Matches, args: ['^SKU-(?P<id>\d+)$']}]}}
+              - single: {input_path: x, functions: [{function: Equal, args: [1]}]}
+        output: {negated: "{{$id}}"}
+      - name: failed_branch
+        conditions:
+          - or:
+              - and:
+                  - single: {input_path: sku, functions: [{function: Matches, args: ['^SKU-(?P<id>\d+)$']}]}
+                  - single: {input_path: x, functions: [{function: Equal, args: [2]}]}
+              - single: {input_path: sku, functions: [{function: Matches, args: ['^(?P<family>[A-Z]+)-']}]}
+        output: {family: "{{$family}}"}
+"#;
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str(rules, Format::Yaml, engine.functions()).unwrap()).unwrap();
+        let input = json!({"sku": "SKU-42", "x": 1});
+
+        let err = engine.evaluate("sku", &input).unwrap_err();
+        assert_eq!(err.to_string(), "rule [negated]: template [{{$id}}]: no group `id` was captured");
+
+        engine.rules.get_mut("sku").unwrap().rules.remove(0);
+        assert_eq!(Value::Object(engine.evaluate("sku", &input).unwrap()), json!({"family": "SKU"}));
+        engine.rules.get_mut("sku").unwrap().rules[0].output.insert("id".to_owned(), json!("{{$id}}"));
+        assert!(engine.evaluate("sku", &input).is_err());
+    }
+
+    #[test]
+    fn evaluate_with_clock() {
+        let rules = r#"
+groups:
+  promotions:
+    hit_policy: Collect
+    rules:
+      - name: adult
+        conditions:
+          - single: {input_path: customer.birth_date, functions: [{function: older_than, args: [18y]}]}
+        output: {eligible: true}
+      - name: summer_sale
+        conditions:
+          - single: {input_path: order.placed_at, functions: [{function: between_dates, args: ['2024-06-01', '2024-06-30']}]}
+          - single: {input_path: customer.last_order, functions: [{function: within_last, args: [30 days]}]}
+        output: {discount: 10}
+"#;
+        let now = "2024-06-15T12:00:00Z".parse().unwrap();
+        let mut engine = Engine::with_clock(Arc::new(FixedClock(now)));
+        engine.add_rule_file(load_str(rules, Format::Yaml, engine.functions()).unwrap()).unwrap();
+
+        let input = json!({
+            "customer": {"birth_date": "2006-06-15", "last_order": "2024-05-20T08:00:00Z"},
+            "order": {"placed_at": "2024-06-30T21:15:00+00:00"},
+        });
+        assert_eq!(Value::Object(engine.evaluate("promotions", &input).unwrap()), json!({"eligible": true, "discount": 10}));
+
+        let input = json!({
+            "customer": {"birth_date": "2006-06-16", "last_order": "2024-05-01"},
+            "order": {"placed_at": "2024-06-30T21:15:00+00:00"},
+        });
+        assert!(engine.evaluate("promotions", &input).unwrap().is_empty());
+    }
+
+    fn engine_with_groups() -> Engine {
+        let mut engine = engine();
+        let fraud = r#"
+groups:
+  fraud:
+    rules:
+      - name: high_total
+        conditions:
+          - single:
+              input_path: total
+              functions:
+                - function: Greater
+                  args: [100]
+        output:
+          review: true
+          tags: [review]
+"#;
+        engine.add_rule_file(load_str(fraud, Format::Yaml, engine.functions()).unwrap()).unwrap();
+        engine
+    }
+
+    #[test]
+    fn list_groups() {
+        let engine = engine_with_groups();
+
+        assert_eq!(engine.groups(), vec!["discounts", "fraud"]);
+        assert_eq!(engine.rules("fraud").unwrap()[0].name, "high_total");
+        assert!(engine.rules("pricing").is_none());
+    }
+
+    #[test]
+    fn evaluate_groups_per_group() {
+        let input = json!({"years": 4, "total": 200.5, "tier": "silver"});
+        let evaluation = engine_with_groups()
+            .evaluate_groups(&["discounts", "fraud", "discounts"], &input, GroupMerge::PerGroup)
+            .unwrap();
+
+        assert_eq!(Value::Object(evaluation.output), json!({
+            "discounts": {"discount": {"loyalty": 5}, "tags": ["loyal"]},
+            "fraud": {"review": true, "tags": ["review"]},
+        }));
+    }
+
+    #[test]
+    fn evaluate_groups_combined() {
+        let input = json!({"years": 4, "total": 200.5, "tier": "silver"});
+        let evaluation = engine_with_groups()
+            .evaluate_groups(&["discounts", "fraud"], &input, GroupMerge::Combined)
+            .unwrap();
+
+        assert_eq!(Value::Object(evaluation.output), json!({
+            "discount": {"loyalty": 5},
+            "review": true,
+            "tags": ["loyal", "review"],
+        }));
+    }
+
+    #[test]
+    fn validate_added_rules() {
+        let mut engine = Engine::new();
+        let rule = |conditions: Value| serde_json::from_value(json!({"name": "bulk", "conditions": conditions})).unwrap();
+        let diagnostics = |err: LogicLoomError| match err {
+            LogicLoomError::InvalidRules(diagnostics) => diagnostics.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            other => panic!("expected invalid rules, got {}", other),
+        };
+
+        let err = engine.add_rule("pricing", rule(json!([{"single": {"input_path": "quantity", "functions": [{"function": "Between", "args": [1]}]}}]))).unwrap_err();
+        assert_eq!(diagnostics(err), vec![
+            "rule `bulk`: groups.pricing.rules[0].conditions[0].single.functions[0].args: `Between` takes 2 arguments but got 1",
+        ]);
+        assert!(engine.rules("pricing").is_none());
+
+        engine.add_rule("pricing", rule(json!([]))).unwrap();
+        let err = engine.add_rules("pricing", [rule(json!([]))]).unwrap_err();
+        assert_eq!(diagnostics(err), vec!["rule `bulk`: groups.pricing.rules[1].name: rule name `bulk` is used more than once in the group"]);
+        let err = engine.add_rule_file(serde_json::from_value(json!({"groups": {"fraud": {"rules": [rule(json!([]))]}}})).unwrap()).unwrap_err();
+        assert_eq!(diagnostics(err), vec!["rule `bulk`: groups.fraud.rules[0].name: rule name `bulk` is also used in group `pricing`"]);
+
+        assert_eq!(engine.groups(), ["pricing"]);
+        assert!(engine.validate().is_empty());
+    }
+
+    #[test]
+    fn reject_rules_with_invalid_patterns() {
+        let mut engine = Engine::new();
+        let rule = |name: &str, pattern: &str| serde_json::from_value(json!({"name": name, "conditions": [
+            {"single": {"input_path": "sku", "functions": [{"function": "Matches", "args": [pattern]}]}}
+        ]})).unwrap();
+
+        let err = engine.add_rules("catalog", [rule("code", "^[A-Z]+$"), rule("sku", "([A-Z]")]).unwrap_err();
+        let LogicLoomError::InvalidRules(diagnostics) = err else { panic!("expected invalid rules, got {}", err) };
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule.as_deref(), Some("sku"));
+        assert!(engine.rules("catalog").is_none());
+
+        engine.add_rule("catalog", rule("code", "^[A-Z]+$")).unwrap();
+        assert_eq!(engine.rules("catalog").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rule_files_keep_hit_policies() {
+        let mut engine = Engine::new();
+        engine.add_rule_file(load_str("groups: {pricing: {hit_policy: First, rules: [{name: a, conditions: []}]}}", Format::Yaml, engine.functions()).unwrap()).unwrap();
+        engine.add_rule_file(load_str("groups: {pricing: {rules: [{name: b, conditions: []}]}}", Format::Yaml, engine.functions()).unwrap()).unwrap();
+        assert_eq!(engine.group("pricing").unwrap().hit_policy(), HitPolicy::First);
+
+        let file = load_str("groups: {pricing: {hit_policy: Collect, rules: [{name: c, conditions: []}]}}", Format::Yaml, engine.functions()).unwrap();
+        let err = engine.add_rule_file(file).unwrap_err();
+        assert_eq!(err.to_string(), "groups.pricing.hit_policy: hit policy `Collect` conflicts with `First`, set for the group before");
+        assert_eq!(engine.rules("pricing").unwrap().len(), 2);
+    }
+}
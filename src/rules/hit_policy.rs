@@ -0,0 +1,175 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use crate::core::{HitPolicy, Rule};
+use crate::error::{LogicLoomError, Result};
+use crate::function::numeric::{numbers_equal, DEFAULT_EPSILON};
+
+/// The rules whose outputs make up the output of a group, and the outputs to merge.
+pub(crate) struct Hits<'a> {
+    pub selected: Vec<&'a Rule>,
+    pub outputs: Vec<(&'a str, Cow<'a, HashMap<String, Value>>)>,
+}
+
+/// A rule that matched, with its rendered output.
+pub(crate) type Match<'a> = (&'a Rule, Cow<'a, HashMap<String, Value>>);
+
+/// The rules that matched, in group order, whose outputs the hit policy of `group` needs: the one with the highest
+/// priority under `Priority`, all of them otherwise. `Unique` fails here when more than one matched. `T` is whatever
+/// the caller needs to render the output of a rule later.
+pub(crate) fn candidates<'a, T>(group: &str, policy: HitPolicy, matches: Vec<(&'a Rule, T)>) -> Result<Vec<(&'a Rule, T)>> {
+    match policy {
+        HitPolicy::Unique if matches.len() > 1 => {
+            let names: Vec<&str> = matches.iter().map(|(rule, _)| rule.name.as_str()).collect();
+            let message = format!("rules [{}] match, at most one may", names.join(", "));
+            Err(LogicLoomError::HitPolicy { group: group.to_owned(), policy, message })
+        }
+        HitPolicy::First => Ok(matches.into_iter().take(1).collect()),
+        // `max_by_key` returns the last maximum, so the rules are searched backwards to get the first one.
+        HitPolicy::Priority => Ok(matches.into_iter().rev().max_by_key(|(rule, _)| rule.priority).into_iter().collect()),
+        _ => Ok(matches),
+    }
+}
+
+/// Applies the hit policy of `group` to the rules that matched, in group order.
+pub(crate) fn apply<'a>(group: &'a str, policy: HitPolicy, matches: Vec<Match<'a>>) -> Result<Hits<'a>> {
+    let error = |message: String| LogicLoomError::HitPolicy { group: group.to_owned(), policy, message };
+
+    let selected = match policy {
+        HitPolicy::Unique | HitPolicy::First | HitPolicy::Priority => candidates(group, policy, matches)?,
+        HitPolicy::Any => {
+            if let Some((other, _)) = matches.iter().find(|(_, output)| *output != matches[0].1) {
+                return Err(error(format!("rules [{}] and [{}] match with different outputs", matches[0].0.name, other.name)));
+            }
+            matches.into_iter().take(1).collect()
+        }
+        HitPolicy::Collect => matches,
+        HitPolicy::CollectSum | HitPolicy::CollectMin | HitPolicy::CollectMax | HitPolicy::CollectCount => {
+            let output = aggregate(policy, &matches).map_err(error)?;
+            let selected = matches.into_iter().map(|(rule, _)| rule).collect();
+            return Ok(Hits { selected, outputs: vec![(group, Cow::Owned(output))] });
+        }
+    };
+
+    let (selected, outputs) = selected.into_iter().map(|(rule, output)| (rule, (rule.name.as_str(), output))).unzip();
+    Ok(Hits { selected, outputs })
+}
+
+// Reduces the values the rules wrote to every output path to one value.
+fn aggregate(policy: HitPolicy, matches: &[Match]) -> std::result::Result<HashMap<String, Value>, String> {
+    let mut paths: BTreeMap<&str, Vec<(&str, &Value)>> = BTreeMap::new();
+    for (rule, output) in matches {
+        for (path, value) in output.iter() {
+            paths.entry(path).or_default().push((&rule.name, value));
+        }
+    }
+
+    let mut output = HashMap::new();
+    for (path, values) in paths {
+        let value = match policy {
+            HitPolicy::CollectCount => {
+                let mut distinct: Vec<&Value> = Vec::new();
+                for (_, value) in values {
+                    if !distinct.iter().any(|seen| numbers_equal(seen, value, DEFAULT_EPSILON)) {
+                        distinct.push(value);
+                    }
+                }
+                Value::from(distinct.len())
+            }
+            _ => {
+                let mut numbers = Vec::with_capacity(values.len());
+                for (rule, value) in values {
+                    match value {
+                        Value::Number(number) => numbers.push(number),
+                        _ => return Err(format!("rule [{}] writes [{}] to [{}], {:?} needs numbers", rule, value, path, policy)),
+                    }
+                }
+                reduce(policy, &numbers)
+            }
+        };
+        output.insert(path.to_owned(), value);
+    }
+    Ok(output)
+}
+
+fn reduce(policy: HitPolicy, numbers: &[&serde_json::Number]) -> Value {
+    let as_f64 = |number: &serde_json::Number| number.as_f64().unwrap_or(f64::NAN);
+    match policy {
+        HitPolicy::CollectMin => numbers.iter().min_by(|a, b| as_f64(a).total_cmp(&as_f64(b))).map_or(Value::Null, |n| Value::Number((*n).clone())),
+        HitPolicy::CollectMax => numbers.iter().max_by(|a, b| as_f64(a).total_cmp(&as_f64(b))).map_or(Value::Null, |n| Value::Number((*n).clone())),
+        _ => {
+            let integers: Option<i64> = numbers.iter().try_fold(0i64, |sum, number| sum.checked_add(number.as_i64()?));
+            match integers {
+                Some(sum) => Value::from(sum),
+                None => Value::from(numbers.iter().map(|number| as_f64(number)).sum::<f64>()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(name: &str, priority: i32, output: Value) -> Rule {
+        serde_json::from_value(json!({"name": name, "priority": priority, "conditions": [], "output": output})).unwrap()
+    }
+
+    fn matched<'a>(rules: impl IntoIterator<Item = &'a Rule>) -> Vec<Match<'a>> {
+        rules.into_iter().map(|rule| (rule, Cow::Borrowed(&rule.output))).collect()
+    }
+
+    fn names<'a>(hits: &Hits<'a>) -> Vec<&'a str> {
+        hits.selected.iter().map(|rule| rule.name.as_str()).collect()
+    }
+
+    #[test]
+    fn single_hit_policies() {
+        let rules = [
+            rule("low", 1, json!({"rate": 5})),
+            rule("high", 10, json!({"rate": 7})),
+            rule("high_too", 10, json!({"rate": 9})),
+        ];
+        let matches = || matched(&rules);
+
+        assert_eq!(names(&apply("tariff", HitPolicy::First, matches()).unwrap()), vec!["low"]);
+        assert_eq!(names(&apply("tariff", HitPolicy::Priority, matches()).unwrap()), vec!["high"]);
+        assert_eq!(names(&apply("tariff", HitPolicy::Collect, matches()).unwrap()), vec!["low", "high", "high_too"]);
+        assert_eq!(names(&apply("tariff", HitPolicy::Unique, matched([&rules[1]])).unwrap()), vec!["high"]);
+        assert!(apply("tariff", HitPolicy::First, Vec::new()).unwrap().outputs.is_empty());
+
+        let err = apply("tariff", HitPolicy::Unique, matches()).err().unwrap();
+        assert_eq!(err.code(), "hit_policy");
+        assert_eq!(err.to_string(), "group [tariff] (Unique): rules [low, high, high_too] match, at most one may");
+
+        let same = rule("same", 0, json!({"rate": 5}));
+        assert_eq!(names(&apply("tariff", HitPolicy::Any, matched([&rules[0], &same])).unwrap()), vec!["low"]);
+        assert!(apply("tariff", HitPolicy::Any, matches()).is_err());
+    }
+
+    #[test]
+    fn collect_hit_policies() {
+        let rules = [
+            rule("a", 0, json!({"fee": 5, "tag": "x"})),
+            rule("b", 0, json!({"fee": 2.5, "tag": "x"})),
+            rule("c", 0, json!({"fee": 7, "tag": "y"})),
+        ];
+        let output = |policy| {
+            let hits = apply("fees", policy, matched(&rules)).unwrap();
+            hits.outputs[0].1.clone().into_owned()
+        };
+
+        assert_eq!(output(HitPolicy::CollectCount), serde_json::from_value(json!({"fee": 3, "tag": 2})).unwrap());
+        let max = apply("fees", HitPolicy::CollectMax, matched(&rules[..2])).err().unwrap();
+        assert_eq!(max.to_string(), "group [fees] (CollectMax): rule [a] writes [\"x\"] to [tag], CollectMax needs numbers");
+
+        let fees = [rule("a", 0, json!({"fee": 5})), rule("b", 0, json!({"fee": 2.5}))];
+        let hits = |policy| apply("fees", policy, matched(&fees)).unwrap().outputs[0].1["fee"].clone();
+        assert_eq!(hits(HitPolicy::CollectSum), json!(7.5));
+        assert_eq!(hits(HitPolicy::CollectMin), json!(2.5));
+        assert_eq!(hits(HitPolicy::CollectMax), json!(5));
+    }
+}
@@ -1 +1,2 @@
-mod engine;
\ No newline at end of file
+pub mod engine;
+mod hit_policy;
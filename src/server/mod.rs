@@ -0,0 +1,327 @@
+use std::sync::{Arc, PoisonError, RwLock, RwLockWriteGuard};
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::LogicLoomError;
+use crate::function::signature::FunctionKind;
+use crate::function::FunctionRegistry;
+use crate::loader::RuleFile;
+use crate::rules::engine::{Evaluation, GroupMerge};
+
+mod rules;
+pub mod store;
+
+use store::{Ruleset, RulesetStore};
+
+/// The state shared by all requests.
+#[derive(Clone)]
+pub struct AppState {
+    store: Arc<RwLock<RulesetStore>>,
+}
+
+impl AppState {
+    /// The state serving `file` with the default functions, unless the engine rejects its rules.
+    pub fn new(file: RuleFile) -> Result<Self, LogicLoomError> {
+        AppState::with_functions(file, FunctionRegistry::default())
+    }
+
+    /// The state serving `file` with `functions`, e.g. the ones `file` was loaded with.
+    pub fn with_functions(file: RuleFile, functions: impl Into<Arc<FunctionRegistry>>) -> Result<Self, LogicLoomError> {
+        Ok(AppState { store: Arc::new(RwLock::new(RulesetStore::new(file, functions.into())?)) })
+    }
+
+    /// Keeps the latest `versions` versions of the rules, see `RulesetStore::set_retained_versions`.
+    pub fn set_retained_versions(&self, versions: usize) {
+        self.store_mut().set_retained_versions(versions);
+    }
+
+    /// The version of the rules a request works with from start to end.
+    pub fn current(&self) -> Arc<Ruleset> {
+        self.store.read().unwrap_or_else(PoisonError::into_inner).current()
+    }
+
+    // A store is only changed by pushing a complete version, so it is consistent even if a writer panicked.
+    fn store_mut(&self) -> RwLockWriteGuard<'_, RulesetStore> {
+        self.store.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The routes of the server:
+///
+/// - `POST /groups/{group}/evaluate`, with the input document as body and `?trace=true` for the trace
+/// - `POST /evaluate/batch`, with a list of `{"groups": [...], "input": ..., "merge": ...}` requests as body
+/// - `GET /groups`, `GET|PUT|DELETE /groups/{group}`, `GET|POST /groups/{group}/rules` and
+///   `GET|PUT|DELETE /groups/{group}/rules/{rule}` to manage the rules; every change creates a new version
+/// - `GET /rulesets`, `GET /rulesets/{version}` and `POST /rulesets/{version}/rollback` for the versions
+/// - `GET /functions` for the functions rules can call, `?kind=single|list|aggregate` for one kind
+/// - `GET /health` and `GET /ready`
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/groups/{group}/evaluate", post(evaluate))
+        .route("/evaluate/batch", post(evaluate_batch))
+        .merge(rules::routes())
+        .route("/functions", get(functions))
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .with_state(state)
+}
+
+/// An error response, serialized as `{"error": {"code": ..., "message": ..., "location": ...}}`.
+#[derive(Debug)]
+pub enum ApiError {
+    Engine(Box<LogicLoomError>),
+    /// The request body is not the JSON the endpoint expects.
+    InvalidRequest(String),
+    /// A rule or ruleset version that does not exist; unknown groups are `Engine` errors.
+    NotFound { code: &'static str, message: String },
+    /// The request would overwrite something it should not, e.g. add a rule whose name is taken.
+    Conflict(String),
+}
+
+impl ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound { .. } => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Engine(e) => match e.as_ref() {
+                LogicLoomError::UnknownGroup { .. } => StatusCode::NOT_FOUND,
+                LogicLoomError::RuleLoad(_) | LogicLoomError::InvalidRules(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            },
+        }
+    }
+
+    fn body(&self) -> Value {
+        match self {
+            ApiError::Engine(e) => json!({"error": e}),
+            ApiError::InvalidRequest(message) => json!({"error": {"code": "invalid_request", "message": message}}),
+            ApiError::NotFound { code, message } => json!({"error": {"code": code, "message": message}}),
+            ApiError::Conflict(message) => json!({"error": {"code": "conflict", "message": message}}),
+        }
+    }
+}
+
+impl From<LogicLoomError> for ApiError {
+    fn from(e: LogicLoomError) -> Self {
+        ApiError::Engine(Box::new(e))
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::InvalidRequest(rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self.body())).into_response()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct EvaluateParams {
+    #[serde(default)]
+    trace: bool,
+}
+
+async fn evaluate(
+    State(state): State<AppState>,
+    Path(group): Path<String>,
+    Query(params): Query<EvaluateParams>,
+    input: Result<Json<Value>, JsonRejection>,
+) -> Result<Json<Evaluation>, ApiError> {
+    let Json(input) = input?;
+    let ruleset = state.current();
+    let evaluation = match params.trace {
+        true => ruleset.engine.explain(&group, &input)?,
+        false => ruleset.engine.evaluate_detailed(&group, &input)?,
+    };
+    Ok(Json(evaluation))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchRequest {
+    groups: Vec<String>,
+    input: Value,
+    /// Defaults to `Combined` for a single group and to `PerGroup` for several.
+    merge: Option<GroupMerge>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchResult {
+    Evaluation(Evaluation),
+    Error { error: LogicLoomError },
+}
+
+/// Evaluates every request on its own; a failing request does not fail the others.
+async fn evaluate_batch(
+    State(state): State<AppState>,
+    requests: Result<Json<Vec<BatchRequest>>, JsonRejection>,
+) -> Result<Json<Vec<BatchResult>>, ApiError> {
+    let Json(requests) = requests?;
+    // The whole batch is evaluated against the same version.
+    let ruleset = state.current();
+    let results = requests.iter()
+        .map(|request| {
+            let groups: Vec<&str> = request.groups.iter().map(String::as_str).collect();
+            let merge = request.merge.unwrap_or(match groups.len() {
+                1 => GroupMerge::Combined,
+                _ => GroupMerge::PerGroup,
+            });
+            match ruleset.engine.evaluate_groups(&groups, &request.input, merge) {
+                Ok(evaluation) => BatchResult::Evaluation(evaluation),
+                Err(error) => BatchResult::Error { error },
+            }
+        })
+        .collect();
+    Ok(Json(results))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct FunctionsParams {
+    kind: Option<FunctionKind>,
+}
+
+async fn functions(State(state): State<AppState>, Query(params): Query<FunctionsParams>) -> Json<Value> {
+    let ruleset = state.current();
+    let signatures = match params.kind {
+        Some(kind) => ruleset.engine.functions().signatures_of(kind),
+        None => ruleset.engine.functions().signatures(),
+    };
+    Json(json!(signatures))
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({"status": "ok"}))
+}
+
+/// Ready once rules are loaded.
+async fn ready(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let groups = state.current().engine.groups().len();
+    match groups {
+        0 => (StatusCode::SERVICE_UNAVAILABLE, Json(json!({"status": "no rules loaded", "groups": 0}))),
+        _ => (StatusCode::OK, Json(json!({"status": "ready", "groups": groups}))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::{load_str, Format};
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Method, Request};
+    use tower::ServiceExt;
+
+    const RULES: &str = r#"
+groups:
+  pricing:
+    rules:
+      - name: bulk
+        conditions:
+          - single: {input_path: quantity, functions: [{function: Greater, args: [10]}]}
+        output:
+          discount: 5
+  fraud:
+    rules:
+      - name: high_total
+        conditions:
+          - single: {input_path: total, functions: [{function: Greater, args: [1000]}]}
+        output:
+          review: true
+"#;
+
+    pub(super) fn app() -> Router {
+        router(AppState::new(load_str(RULES, Format::Yaml, &FunctionRegistry::default()).unwrap()).unwrap())
+    }
+
+    pub(super) async fn call(app: Router, method: Method, uri: &str, body: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_owned()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn evaluate_group() {
+        let (status, body) = call(app(), Method::POST, "/groups/pricing/evaluate", r#"{"quantity": 12}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"output": {"discount": 5}, "errors": [], "conflicts": []}));
+
+        let (_, body) = call(app(), Method::POST, "/groups/pricing/evaluate?trace=true", r#"{"quantity": 12}"#).await;
+        assert_eq!(body["trace"][0]["rule"], json!("bulk"));
+        assert_eq!(body["trace"][0]["matched"], json!(true));
+    }
+
+    #[tokio::test]
+    async fn evaluate_errors() {
+        let (status, body) = call(app(), Method::POST, "/groups/shipping/evaluate", "{}").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"]["code"], json!("unknown_group"));
+
+        let (status, body) = call(app(), Method::POST, "/groups/pricing/evaluate", r#"{"quantity": [1]}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["code"], json!("type_mismatch"));
+        assert_eq!(body["error"]["location"], json!({"rule": "bulk", "input_path": "quantity", "function": "Greater"}));
+
+        let (status, body) = call(app(), Method::POST, "/groups/pricing/evaluate", "{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], json!("invalid_request"));
+    }
+
+    #[tokio::test]
+    async fn evaluate_batch_requests() {
+        let requests = r#"[
+            {"groups": ["pricing"], "input": {"quantity": 12}},
+            {"groups": ["pricing", "fraud"], "input": {"quantity": 1, "total": 5000}},
+            {"groups": ["shipping"], "input": {}}
+        ]"#;
+        let (status, body) = call(app(), Method::POST, "/evaluate/batch", requests).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["output"], json!({"discount": 5}));
+        assert_eq!(body[1]["output"], json!({"pricing": {}, "fraud": {"review": true}}));
+        assert_eq!(body[2]["error"]["code"], json!("unknown_group"));
+    }
+
+    #[tokio::test]
+    async fn list_functions() {
+        let (status, body) = call(app(), Method::GET, "/functions", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["name"], json!("After"));
+        assert_eq!(body.as_array().unwrap().len(), FunctionRegistry::default().signatures().len());
+
+        let (_, body) = call(app(), Method::GET, "/functions?kind=aggregate", "").await;
+        let names: Vec<&str> = body.as_array().unwrap().iter().map(|signature| signature["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["AVG", "COUNT", "MAX", "MIN", "SUM"]);
+    }
+
+    #[tokio::test]
+    async fn health_and_readiness() {
+        let (status, _) = call(app(), Method::GET, "/health", "").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call(app(), Method::GET, "/ready", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["groups"], json!(2));
+
+        let (status, _) = call(router(AppState::new(RuleFile::default()).unwrap()), Method::GET, "/ready", "").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,304 @@
+use std::sync::{Arc, PoisonError};
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::core::{HitPolicy, Rule, RuleGroup};
+use crate::error::LogicLoomError;
+use crate::loader::{load_group, load_rule, RuleFile};
+
+use super::store::{Ruleset, VersionSummary};
+use super::{ApiError, AppState};
+
+pub(super) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/groups", get(list_groups))
+        .route("/groups/{group}", get(get_group).put(put_group).delete(delete_group))
+        .route("/groups/{group}/rules", get(list_rules).post(add_rule))
+        .route("/groups/{group}/rules/{rule}", get(get_rule).put(put_rule).delete(delete_rule))
+        .route("/rulesets", get(list_versions))
+        .route("/rulesets/{version}", get(get_version))
+        .route("/rulesets/{version}/rollback", post(rollback))
+}
+
+/// The answer to a change: the version it created.
+#[derive(Serialize)]
+struct Changed {
+    version: u64,
+    description: String,
+}
+
+impl Changed {
+    fn new(ruleset: &Ruleset) -> Json<Changed> {
+        Json(Changed { version: ruleset.version, description: ruleset.description.clone() })
+    }
+}
+
+#[derive(Serialize)]
+struct GroupSummary<'a> {
+    name: &'a str,
+    hit_policy: HitPolicy,
+    rules: Vec<&'a str>,
+}
+
+async fn list_groups(State(state): State<AppState>) -> Json<Value> {
+    let ruleset = state.current();
+    let groups: Vec<GroupSummary> = ruleset.file.groups.iter()
+        .map(|(name, group)| GroupSummary {
+            name,
+            hit_policy: group.hit_policy(),
+            rules: group.rules.iter().map(|rule| rule.name.as_str()).collect(),
+        })
+        .collect();
+    Json(serde_json::to_value(groups).unwrap_or_default())
+}
+
+fn unknown_group(group: &str) -> ApiError {
+    LogicLoomError::UnknownGroup { group: group.to_owned() }.into()
+}
+
+fn unknown_rule(group: &str, rule: &str) -> ApiError {
+    ApiError::NotFound { code: "unknown_rule", message: format!("no rule [{}] in group [{}]", rule, group) }
+}
+
+fn group_mut<'a>(file: &'a mut RuleFile, group: &str) -> Result<&'a mut RuleGroup, ApiError> {
+    file.groups.get_mut(group).ok_or_else(|| unknown_group(group))
+}
+
+// Rule names are unique across all groups: a conflict when a group other than `except` has a rule named `name`.
+fn name_taken(file: &RuleFile, name: &str, except: Option<&str>) -> Result<(), ApiError> {
+    let mut others = file.groups.iter().filter(|(group, _)| Some(group.as_str()) != except);
+    match others.find(|(_, rule_group)| rule_group.rules.iter().any(|rule| rule.name == name)) {
+        Some((group, _)) => Err(ApiError::Conflict(format!("group [{}] already has a rule [{}]", group, name))),
+        None => Ok(()),
+    }
+}
+
+fn rule_index(group: &RuleGroup, group_name: &str, rule: &str) -> Result<usize, ApiError> {
+    group.rules.iter().position(|r| r.name == rule).ok_or_else(|| unknown_rule(group_name, rule))
+}
+
+async fn get_group(State(state): State<AppState>, Path(group): Path<String>) -> Result<Json<RuleGroup>, ApiError> {
+    state.current().file.groups.get(&group).cloned().map(Json).ok_or_else(|| unknown_group(&group))
+}
+
+/// Creates or replaces a group.
+async fn put_group(
+    State(state): State<AppState>,
+    Path(group): Path<String>,
+    body: Result<Json<Value>, JsonRejection>,
+) -> Result<(StatusCode, Json<Changed>), ApiError> {
+    let Json(body) = body?;
+    let rule_group = load_group(&body, state.current().engine.functions())?;
+    let (created, ruleset) = state.store_mut().update(format!("put group [{}]", group), |file| -> Result<bool, ApiError> {
+        for rule in &rule_group.rules {
+            name_taken(file, &rule.name, Some(&group))?;
+        }
+        Ok(file.groups.insert(group.clone(), rule_group).is_none())
+    })?;
+    let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, Changed::new(&ruleset)))
+}
+
+async fn delete_group(State(state): State<AppState>, Path(group): Path<String>) -> Result<Json<Changed>, ApiError> {
+    let ((), ruleset) = state.store_mut().update(format!("delete group [{}]", group), |file| {
+        file.groups.remove(&group).map(|_| ()).ok_or_else(|| unknown_group(&group))
+    })?;
+    Ok(Changed::new(&ruleset))
+}
+
+async fn list_rules(State(state): State<AppState>, Path(group): Path<String>) -> Result<Json<Vec<Rule>>, ApiError> {
+    state.current().file.groups.get(&group).map(|group| Json(group.rules.clone())).ok_or_else(|| unknown_group(&group))
+}
+
+/// Appends a rule to a group; the group is created when it does not exist.
+async fn add_rule(
+    State(state): State<AppState>,
+    Path(group): Path<String>,
+    body: Result<Json<Value>, JsonRejection>,
+) -> Result<(StatusCode, Json<Changed>), ApiError> {
+    let Json(body) = body?;
+    let rule = load_rule(&body, state.current().engine.functions())?;
+    let description = format!("add rule [{}] to group [{}]", rule.name, group);
+    let ((), ruleset) = state.store_mut().update(description, |file| -> Result<(), ApiError> {
+        name_taken(file, &rule.name, None)?;
+        file.groups.entry(group.clone()).or_default().rules.push(rule);
+        Ok(())
+    })?;
+    Ok((StatusCode::CREATED, Changed::new(&ruleset)))
+}
+
+async fn get_rule(State(state): State<AppState>, Path((group, rule)): Path<(String, String)>) -> Result<Json<Rule>, ApiError> {
+    let ruleset = state.current();
+    let rule_group = ruleset.file.groups.get(&group).ok_or_else(|| unknown_group(&group))?;
+    let index = rule_index(rule_group, &group, &rule)?;
+    Ok(Json(rule_group.rules[index].clone()))
+}
+
+/// Replaces a rule, keeping its position in the group.
+async fn put_rule(
+    State(state): State<AppState>,
+    Path((group, name)): Path<(String, String)>,
+    body: Result<Json<Value>, JsonRejection>,
+) -> Result<Json<Changed>, ApiError> {
+    let Json(body) = body?;
+    let rule = load_rule(&body, state.current().engine.functions())?;
+    if rule.name != name {
+        return Err(ApiError::InvalidRequest(format!("the rule is named [{}] but the path names [{}]", rule.name, name)));
+    }
+    let ((), ruleset) = state.store_mut().update(format!("put rule [{}] in group [{}]", name, group), |file| -> Result<(), ApiError> {
+        let rule_group = group_mut(file, &group)?;
+        let index = rule_index(rule_group, &group, &name)?;
+        rule_group.rules[index] = rule;
+        Ok(())
+    })?;
+    Ok(Changed::new(&ruleset))
+}
+
+async fn delete_rule(State(state): State<AppState>, Path((group, name)): Path<(String, String)>) -> Result<Json<Changed>, ApiError> {
+    let ((), ruleset) = state.store_mut().update(format!("delete rule [{}] from group [{}]", name, group), |file| -> Result<(), ApiError> {
+        let rule_group = group_mut(file, &group)?;
+        let index = rule_index(rule_group, &group, &name)?;
+        rule_group.rules.remove(index);
+        Ok(())
+    })?;
+    Ok(Changed::new(&ruleset))
+}
+
+async fn list_versions(State(state): State<AppState>) -> Json<Value> {
+    let store = state.store.read().unwrap_or_else(PoisonError::into_inner);
+    let current = store.current().version;
+    let versions: Vec<VersionSummary> = store.versions().iter()
+        .map(|ruleset| ruleset.summary(ruleset.version == current))
+        .collect();
+    Json(serde_json::to_value(versions).unwrap_or_default())
+}
+
+fn unknown_version(version: u64) -> ApiError {
+    ApiError::NotFound { code: "unknown_version", message: format!("no ruleset version [{}]", version) }
+}
+
+fn version(state: &AppState, version: u64) -> Result<Arc<Ruleset>, ApiError> {
+    state.store.read().unwrap_or_else(PoisonError::into_inner).version(version).ok_or_else(|| unknown_version(version))
+}
+
+/// The rules of a version, in the format of a rule file.
+async fn get_version(State(state): State<AppState>, Path(number): Path<u64>) -> Result<Json<RuleFile>, ApiError> {
+    Ok(Json(version(&state, number)?.file.clone()))
+}
+
+async fn rollback(State(state): State<AppState>, Path(number): Path<u64>) -> Result<Json<Changed>, ApiError> {
+    let ruleset = state.store_mut().rollback(number)?.ok_or_else(|| unknown_version(number))?;
+    Ok(Changed::new(&ruleset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{app, call};
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+
+    const RULE: &str = r#"{"name": "loyal", "conditions": [{"single": {"input_path": "years", "functions": [{"function": "GreaterEq", "args": [3]}]}}], "output": {"discount": 7}}"#;
+
+    #[tokio::test]
+    async fn manage_rules_and_groups() {
+        let app = app();
+
+        let (status, body) = call(app.clone(), Method::GET, "/groups", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!([
+            {"name": "fraud", "hit_policy": "Collect", "rules": ["high_total"]},
+            {"name": "pricing", "hit_policy": "Collect", "rules": ["bulk"]},
+        ]));
+
+        let (status, body) = call(app.clone(), Method::POST, "/groups/pricing/rules", RULE).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["version"], json!(2));
+        let (status, body) = call(app.clone(), Method::POST, "/groups/pricing/rules", RULE).await;
+        assert_eq!((status, body["error"]["code"].clone()), (StatusCode::CONFLICT, json!("conflict")));
+        let (status, _) = call(app.clone(), Method::POST, "/groups/fraud/rules", RULE).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (_, body) = call(app.clone(), Method::POST, "/groups/pricing/evaluate", r#"{"quantity": 1, "years": 5}"#).await;
+        assert_eq!(body["output"], json!({"discount": 7}));
+
+        let updated = RULE.replace("7", "9");
+        let (status, _) = call(app.clone(), Method::PUT, "/groups/pricing/rules/loyal", &updated).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = call(app.clone(), Method::GET, "/groups/pricing/rules/loyal", "").await;
+        assert_eq!(body["output"], json!({"discount": 9}));
+        let (status, _) = call(app.clone(), Method::PUT, "/groups/pricing/rules/other", &updated).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = call(app.clone(), Method::DELETE, "/groups/pricing/rules/bulk", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = call(app.clone(), Method::GET, "/groups/pricing/rules", "").await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        let (status, body) = call(app.clone(), Method::DELETE, "/groups/pricing/rules/bulk", "").await;
+        assert_eq!((status, body["error"]["code"].clone()), (StatusCode::NOT_FOUND, json!("unknown_rule")));
+
+        let group = format!(r#"{{"hit_policy": "First", "rules": [{}]}}"#, RULE.replace("loyal", "loyal_shipping"));
+        let (status, body) = call(app.clone(), Method::PUT, "/groups/shipping", &format!(r#"{{"rules": [{}]}}"#, RULE)).await;
+        assert_eq!((status, body["error"]["code"].clone()), (StatusCode::CONFLICT, json!("conflict")));
+        assert_eq!(body["error"]["message"], json!("group [pricing] already has a rule [loyal]"));
+        let (status, _) = call(app.clone(), Method::PUT, "/groups/shipping", &group).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (_, body) = call(app.clone(), Method::GET, "/groups/shipping", "").await;
+        assert_eq!(body["hit_policy"], json!("First"));
+        // Replacing a group may keep the names of its own rules.
+        let (status, _) = call(app.clone(), Method::PUT, "/groups/shipping", &group).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(app.clone(), Method::DELETE, "/groups/shipping", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = call(app.clone(), Method::GET, "/groups/shipping", "").await;
+        assert_eq!((status, body["error"]["code"].clone()), (StatusCode::NOT_FOUND, json!("unknown_group")));
+    }
+
+    #[tokio::test]
+    async fn invalid_rules_are_rejected() {
+        let app = app();
+        let (status, body) = call(app.clone(), Method::POST, "/groups/pricing/rules", r#"{"name": "x", "conditions": [], "outputs": {}}"#).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], json!("rule_load"));
+        assert!(body["error"]["message"].as_str().unwrap().starts_with("rule `x`: outputs: unknown field `outputs`"));
+
+        let rule = r#"{"name": "x", "conditions": [{"single": {"input_path": "total", "functions": [{"function": "Greater", "args": ["many"]}]}}]}"#;
+        let (status, body) = call(app.clone(), Method::POST, "/groups/pricing/rules", rule).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], json!("invalid_rules"));
+        assert_eq!(body["error"]["message"], json!("rule `x`: conditions[0].single.functions[0].args[0]: `Greater` takes number arguments but got a string that is not a number [\"many\"]"));
+
+        let (_, body) = call(app, Method::GET, "/rulesets", "").await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn list_and_roll_back_versions() {
+        let app = app();
+        call(app.clone(), Method::DELETE, "/groups/pricing", "").await;
+
+        let (_, body) = call(app.clone(), Method::GET, "/rulesets", "").await;
+        assert_eq!(body.as_array().unwrap().iter().map(|v| (v["version"].clone(), v["current"].clone())).collect::<Vec<_>>(),
+            vec![(json!(1), json!(false)), (json!(2), json!(true))]);
+        assert_eq!(body[1]["description"], json!("delete group [pricing]"));
+
+        let (status, _) = call(app.clone(), Method::POST, "/groups/pricing/evaluate", "{}").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = call(app.clone(), Method::POST, "/rulesets/1/rollback", "").await;
+        assert_eq!((status, body["version"].clone()), (StatusCode::OK, json!(3)));
+        let (_, body) = call(app.clone(), Method::POST, "/groups/pricing/evaluate", r#"{"quantity": 20}"#).await;
+        assert_eq!(body["output"], json!({"discount": 5}));
+
+        let (_, body) = call(app.clone(), Method::GET, "/rulesets/2", "").await;
+        assert_eq!(body["groups"].as_object().unwrap().keys().collect::<Vec<_>>(), vec!["fraud"]);
+        let (status, body) = call(app, Method::POST, "/rulesets/9/rollback", "").await;
+        assert_eq!((status, body["error"]["code"].clone()), (StatusCode::NOT_FOUND, json!("unknown_version")));
+    }
+}
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::error::{LogicLoomError, Result};
+use crate::function::FunctionRegistry;
+use crate::loader::RuleFile;
+use crate::rules::engine::Engine;
+
+/// One version of the served rules. It is never changed, a change to the rules creates a new version.
+pub struct Ruleset {
+    pub version: u64,
+    pub description: String,
+    /// Seconds since the Unix epoch.
+    pub created_at: u64,
+    pub file: RuleFile,
+    pub engine: Engine,
+}
+
+impl Ruleset {
+    fn new(version: u64, description: String, file: RuleFile, functions: Arc<FunctionRegistry>) -> Result<Self> {
+        let mut engine = Engine::with_functions(functions);
+        engine.add_rule_file(file.clone())?;
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        Ok(Ruleset { version, description, created_at, file, engine })
+    }
+
+    pub fn summary(&self, current: bool) -> VersionSummary<'_> {
+        VersionSummary {
+            version: self.version,
+            description: &self.description,
+            created_at: self.created_at,
+            groups: self.file.groups.len(),
+            current,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionSummary<'a> {
+    pub version: u64,
+    pub description: &'a str,
+    pub created_at: u64,
+    pub groups: usize,
+    pub current: bool,
+}
+
+/// How many versions a store keeps unless told otherwise, see `RulesetStore::set_retained_versions`.
+pub const DEFAULT_RETAINED_VERSIONS: usize = 100;
+
+/// The latest versions of the rules, the last one being served. Evaluations hold on to the `Arc` of the version they
+/// started with, so a change never affects an evaluation in progress, and an older version is dropped once it is not
+/// retained and no evaluation uses it anymore.
+pub struct RulesetStore {
+    versions: Vec<Arc<Ruleset>>,
+    retained: usize,
+    // Shared by the engines of all versions, so a pattern is compiled once.
+    functions: Arc<FunctionRegistry>,
+}
+
+impl RulesetStore {
+    /// A store serving `file` as version 1 with `functions`, unless the engine rejects its rules.
+    pub fn new(file: RuleFile, functions: Arc<FunctionRegistry>) -> Result<Self> {
+        let initial = Ruleset::new(1, "initial rules".to_owned(), file, functions.clone())?;
+        Ok(RulesetStore { versions: vec![Arc::new(initial)], retained: DEFAULT_RETAINED_VERSIONS, functions })
+    }
+
+    /// Keeps the latest `versions` versions, at least the current one, and drops older ones; they can not be rolled
+    /// back to anymore.
+    pub fn set_retained_versions(&mut self, versions: usize) {
+        self.retained = versions.max(1);
+        self.prune();
+    }
+
+    fn prune(&mut self) {
+        let dropped = self.versions.len().saturating_sub(self.retained);
+        self.versions.drain(..dropped);
+    }
+
+    pub fn current(&self) -> Arc<Ruleset> {
+        self.versions.last().cloned().expect("the store always holds a version")
+    }
+
+    pub fn versions(&self) -> &[Arc<Ruleset>] {
+        &self.versions
+    }
+
+    pub fn version(&self, version: u64) -> Option<Arc<Ruleset>> {
+        self.versions.iter().find(|ruleset| ruleset.version == version).cloned()
+    }
+
+    /// Applies `change` to a copy of the current rules and serves the result as a new version,
+    /// unless `change` fails or the engine rejects the changed rules.
+    pub fn update<T, E: From<LogicLoomError>>(
+        &mut self,
+        description: String,
+        change: impl FnOnce(&mut RuleFile) -> std::result::Result<T, E>,
+    ) -> std::result::Result<(T, Arc<Ruleset>), E> {
+        let mut file = self.current().file.clone();
+        let result = change(&mut file)?;
+        Ok((result, self.push(description, file)?))
+    }
+
+    /// Serves the rules of an earlier version again, as a new version; `None` for a version that does not exist.
+    pub fn rollback(&mut self, version: u64) -> Result<Option<Arc<Ruleset>>> {
+        let Some(ruleset) = self.version(version) else {
+            return Ok(None);
+        };
+        self.push(format!("rollback to version {}", version), ruleset.file.clone()).map(Some)
+    }
+
+    fn push(&mut self, description: String, file: RuleFile) -> Result<Arc<Ruleset>> {
+        let version = self.current().version + 1;
+        let ruleset = Arc::new(Ruleset::new(version, description, file, self.functions.clone())?);
+        self.versions.push(ruleset.clone());
+        self.prune();
+        Ok(ruleset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::{load_str, Format};
+    use serde_json::json;
+
+    const RULES: &str = r#"
+groups:
+  pricing:
+    rules:
+      - name: bulk
+        conditions: []
+        output:
+          discount: 5
+"#;
+
+    #[test]
+    fn updates_create_versions() {
+        let functions = Arc::new(FunctionRegistry::default());
+        let mut store = RulesetStore::new(load_str(RULES, Format::Yaml, &functions).unwrap(), functions).unwrap();
+        let before = store.current();
+
+        let ((), after) = store.update::<_, LogicLoomError>("more discount".to_owned(), |file| {
+            file.groups.get_mut("pricing").unwrap().rules[0].output.insert("discount".to_owned(), json!(10));
+            Ok(())
+        }).unwrap();
+
+        assert_eq!((before.version, after.version), (1, 2));
+        // The version an evaluation started with is not affected.
+        assert_eq!(before.engine.evaluate("pricing", &json!({})).unwrap()["discount"], json!(5));
+        assert_eq!(store.current().engine.evaluate("pricing", &json!({})).unwrap()["discount"], json!(10));
+
+        assert!(store.update("failing change".to_owned(), |_| Err::<(), _>(LogicLoomError::UnknownGroup { group: "x".to_owned() })).is_err());
+        assert_eq!(store.versions().len(), 2);
+
+        let rolled_back = store.rollback(1).unwrap().unwrap();
+        assert_eq!(rolled_back.version, 3);
+        assert_eq!(rolled_back.description, "rollback to version 1");
+        assert_eq!(store.current().engine.evaluate("pricing", &json!({})).unwrap()["discount"], json!(5));
+        assert!(store.rollback(7).unwrap().is_none());
+    }
+
+    #[test]
+    fn old_versions_are_dropped() {
+        let functions = Arc::new(FunctionRegistry::default());
+        let mut store = RulesetStore::new(load_str(RULES, Format::Yaml, &functions).unwrap(), functions).unwrap();
+        let first = store.current();
+        store.set_retained_versions(2);
+        for discount in [6, 7, 8] {
+            store.update::<_, LogicLoomError>(format!("discount {}", discount), |file| {
+                file.groups.get_mut("pricing").unwrap().rules[0].output.insert("discount".to_owned(), json!(discount));
+                Ok(())
+            }).unwrap();
+        }
+
+        assert_eq!(store.versions().iter().map(|ruleset| ruleset.version).collect::<Vec<_>>(), [3, 4]);
+        assert!(store.rollback(2).unwrap().is_none());
+        // A version in use outlives the store's reference to it.
+        assert_eq!(first.engine.evaluate("pricing", &json!({})).unwrap()["discount"], json!(5));
+
+        store.set_retained_versions(0);
+        assert_eq!(store.versions().len(), 1);
+        assert_eq!(store.current().version, 4);
+    }
+}
@@ -0,0 +1,331 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use serde_json::{Number, Value};
+
+use crate::error::{LogicLoomError, Location, Result};
+use crate::function::pattern::Captures;
+use crate::function::to_number;
+use crate::path;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// Renders the placeholders in the values of a rule output, or borrows the output when it has none.
+/// `$name` and `$1` in an expression refer to the groups captured by the rule's regular expressions.
+pub fn render_output<'a>(output: &'a HashMap<String, Value>, input: &Value, captures: &Captures) -> Result<Cow<'a, HashMap<String, Value>>> {
+    if !output.values().any(has_placeholders) {
+        return Ok(Cow::Borrowed(output));
+    }
+    let scope = Scope { input, captures };
+    let mut rendered = HashMap::with_capacity(output.len());
+    for (key, value) in output {
+        rendered.insert(key.clone(), scope.render(value)?);
+    }
+    Ok(Cow::Owned(rendered))
+}
+
+/// Replaces every `{{expression}}` in the strings of `value` with the expression evaluated against `input`.
+/// A string that is one placeholder and nothing else becomes the value of the expression, keeping its type.
+pub fn render(value: &Value, input: &Value) -> Result<Value> {
+    Scope { input, captures: &Captures::new() }.render(value)
+}
+
+/// Like `render`, with captured regular expression groups.
+pub fn render_with_captures(value: &Value, input: &Value, captures: &Captures) -> Result<Value> {
+    Scope { input, captures }.render(value)
+}
+
+// What the paths and captures of expressions are resolved against.
+#[derive(Clone, Copy)]
+struct Scope<'a> {
+    input: &'a Value,
+    captures: &'a Captures,
+}
+
+impl Scope<'_> {
+    fn render(self, value: &Value) -> Result<Value> {
+        match value {
+            Value::String(template) => render_str(template, self),
+            Value::Array(elements) => elements.iter().map(|element| self.render(element)).collect(),
+            Value::Object(object) => object.iter()
+                .map(|(key, value)| Ok((key.clone(), self.render(value)?)))
+                .collect(),
+            value => Ok(value.clone()),
+        }
+    }
+}
+
+pub fn has_placeholders(value: &Value) -> bool {
+    match value {
+        Value::String(template) => template.contains(OPEN),
+        Value::Array(elements) => elements.iter().any(has_placeholders),
+        Value::Object(object) => object.values().any(has_placeholders),
+        _ => false,
+    }
+}
+
+fn render_str(template: &str, scope: Scope) -> Result<Value> {
+    if let Some(expression) = template.strip_prefix(OPEN).and_then(|rest| rest.strip_suffix(CLOSE)) {
+        if !expression.contains(OPEN) && !expression.contains(CLOSE) {
+            return evaluate(expression, scope).map_err(|e| in_template(e, template));
+        }
+    }
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(OPEN) {
+        rendered.push_str(&rest[..start]);
+        let after_open = &rest[start + OPEN.len()..];
+        let end = after_open.find(CLOSE)
+            .ok_or_else(|| template_error(template, "missing `}}`"))?;
+        match evaluate(&after_open[..end], scope).map_err(|e| in_template(e, template))? {
+            Value::String(string) => rendered.push_str(&string),
+            value => rendered.push_str(&value.to_string()),
+        }
+        rest = &after_open[end + CLOSE.len()..];
+    }
+    rendered.push_str(rest);
+    Ok(Value::String(rendered))
+}
+
+fn template_error(template: &str, message: impl Into<String>) -> LogicLoomError {
+    LogicLoomError::Template { location: Location::default(), template: template.into(), message: message.into() }
+}
+
+fn in_template(err: LogicLoomError, template: &str) -> LogicLoomError {
+    match err {
+        LogicLoomError::Template { location, message, .. } => LogicLoomError::Template { location, template: template.into(), message },
+        err => err,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    Number(&'a str),
+    Path(&'a str),
+    Capture(&'a str),
+    Operator(char),
+    Open,
+    Close,
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token<'_>>> {
+    let is_path_start = |c: char| c.is_alphabetic() || c == '_' || c == '[';
+    let is_path_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '.' | '[' | ']');
+
+    let mut tokens = Vec::new();
+    let mut chars = expression.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut take_while = |f: &dyn Fn(char) -> bool| {
+            let mut end = start + c.len_utf8();
+            while let Some(&(i, c)) = chars.peek() {
+                if !f(c) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            &expression[start..end]
+        };
+        match c {
+            c if c.is_whitespace() => {}
+            '+' | '-' | '*' | '/' | '%' => tokens.push(Token::Operator(c)),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_ascii_digit() => tokens.push(Token::Number(take_while(&|c| c.is_ascii_digit() || c == '.'))),
+            c if is_path_start(c) => tokens.push(Token::Path(take_while(&is_path_char))),
+            '$' => tokens.push(Token::Capture(&take_while(&|c| c.is_alphanumeric() || c == '_')[1..])),
+            c => return Err(template_error(expression, format!("unexpected character `{}`", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Evaluates `path`, `$capture`, `number`, `( expression )`, unary minus and `+ - * / %` with the usual precedence.
+fn evaluate(expression: &str, scope: Scope) -> Result<Value> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser { expression, tokens: &tokens, position: 0, scope };
+    let value = parser.sum()?;
+    match parser.tokens.get(parser.position) {
+        None => Ok(value),
+        Some(token) => Err(parser.error(format!("unexpected {:?}", token))),
+    }
+}
+
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: &'a [Token<'a>],
+    position: usize,
+    scope: Scope<'a>,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> LogicLoomError {
+        template_error(self.expression, message)
+    }
+
+    fn next_operator(&mut self, operators: &[char]) -> Option<char> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(operator)) if operators.contains(operator) => {
+                self.position += 1;
+                Some(*operator)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<Value> {
+        let mut value = self.product()?;
+        while let Some(operator) = self.next_operator(&['+', '-']) {
+            let right = self.product()?;
+            value = self.apply(operator, &value, &right)?;
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<Value> {
+        let mut value = self.unary()?;
+        while let Some(operator) = self.next_operator(&['*', '/', '%']) {
+            let right = self.unary()?;
+            value = self.apply(operator, &value, &right)?;
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<Value> {
+        if self.next_operator(&['-']).is_some() {
+            let value = self.unary()?;
+            return self.apply('-', &Value::from(0), &value);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Value> {
+        let token = self.tokens.get(self.position).ok_or_else(|| self.error("unexpected end of expression"))?;
+        self.position += 1;
+        match token {
+            Token::Number(number) => serde_json::from_str::<Number>(number)
+                .map(Value::Number)
+                .map_err(|_| self.error(format!("invalid number `{}`", number))),
+            Token::Path(input_path) => Ok(path::resolve(self.scope.input, input_path)?.clone()),
+            Token::Capture(name) => self.scope.captures.get(*name).cloned()
+                .ok_or_else(|| self.error(format!("no group `{}` was captured", name))),
+            Token::Open => {
+                let value = self.sum()?;
+                match self.tokens.get(self.position) {
+                    Some(Token::Close) => {
+                        self.position += 1;
+                        Ok(value)
+                    }
+                    _ => Err(self.error("missing `)`")),
+                }
+            }
+            token => Err(self.error(format!("unexpected {:?}", token))),
+        }
+    }
+
+    // Integers stay integers for `+`, `-`, `*` and `%` as long as the result fits, everything else is computed as a float.
+    fn apply(&self, operator: char, left: &Value, right: &Value) -> Result<Value> {
+        if let (Some(a), Some(b)) = (left.as_i64(), right.as_i64()) {
+            let result = match operator {
+                '+' => a.checked_add(b),
+                '-' => a.checked_sub(b),
+                '*' => a.checked_mul(b),
+                '%' if b != 0 => a.checked_rem(b),
+                _ => None,
+            };
+            if let Some(result) = result {
+                return Ok(Value::from(result));
+            }
+        }
+
+        let number = |value: &Value| to_number(value)
+            .ok_or_else(|| self.error(format!("[{}] is not a number", value)));
+        let (a, b) = (number(left)?, number(right)?);
+        if matches!(operator, '/' | '%') && b == 0.0 {
+            return Err(self.error("division by zero"));
+        }
+        let result = match operator {
+            '+' => a + b,
+            '-' => a - b,
+            '*' => a * b,
+            '/' => a / b,
+            _ => a % b,
+        };
+        Number::from_f64(result).map(Value::Number).ok_or_else(|| self.error("result is not a finite number"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn input() -> Value {
+        json!({
+            "customer": {"name": "Ankit", "tags": ["new"]},
+            "order": {"total": 250, "shipping": "12.5", "items": [{"price": 100}, {"price": 150}]},
+        })
+    }
+
+    #[test]
+    fn render_keeps_the_type_of_a_single_placeholder() {
+        let input = input();
+
+        assert_eq!(render(&json!("{{order.total}}"), &input).unwrap(), json!(250));
+        assert_eq!(render(&json!("{{customer.tags}}"), &input).unwrap(), json!(["new"]));
+        assert_eq!(render(&json!("{{ order.total * 0.1 }}"), &input).unwrap(), json!(25.0));
+        assert_eq!(render(&json!("{{order.items[1].price - order.items[0].price}}"), &input).unwrap(), json!(50));
+        assert_eq!(render(&json!(["{{customer.name}}", {"n": "{{order.total}}"}, 7]), &input).unwrap(), json!(["Ankit", {"n": 250}, 7]));
+    }
+
+    #[test]
+    fn render_interpolates_strings() {
+        let input = input();
+
+        assert_eq!(render(&json!("Hello {{customer.name}}!"), &input).unwrap(), json!("Hello Ankit!"));
+        assert_eq!(render(&json!("{{order.total}} + {{order.shipping}}"), &input).unwrap(), json!("250 + 12.5"));
+        assert_eq!(render(&json!("{{order.total}}{{customer.name}}"), &input).unwrap(), json!("250Ankit"));
+        assert_eq!(render(&json!("no placeholders"), &input).unwrap(), json!("no placeholders"));
+    }
+
+    #[test]
+    fn evaluate_arithmetic() {
+        let input = input();
+        let captures = Captures::new();
+        let eval = |expression| evaluate(expression, Scope { input: &input, captures: &captures }).unwrap();
+
+        assert_eq!(eval("1 + 2 * 3"), json!(7));
+        assert_eq!(eval("(1 + 2) * 3"), json!(9));
+        assert_eq!(eval("-order.total + 50"), json!(-200));
+        assert_eq!(eval("order.total / 4"), json!(62.5));
+        assert_eq!(eval("order.total % 7"), json!(5));
+        assert_eq!(eval("order.total + order.shipping"), json!(262.5));
+    }
+
+    #[test]
+    fn render_errors() {
+        let input = input();
+        let err = |template: &str| render(&json!(template), &input).unwrap_err();
+
+        assert_eq!(err("{{order.total / 0}}").to_string(), "template [{{order.total / 0}}]: division by zero");
+        assert_eq!(err("{{customer.name * 2}}").to_string(), "template [{{customer.name * 2}}]: [\"Ankit\"] is not a number");
+        assert_eq!(err("Hi {{customer.name").to_string(), "template [Hi {{customer.name]: missing `}}`");
+        assert_eq!(err("{{(1 + 2}}").code(), "template");
+        assert_eq!(err("{{1 +}}").code(), "template");
+        assert_eq!(err("{{customer.email}}").code(), "missing_path");
+        assert_eq!(err("{{$sku}}").to_string(), "template [{{$sku}}]: no group `sku` was captured");
+    }
+
+    #[test]
+    fn render_captures() {
+        let input = input();
+        let captures = Captures::from([("0".to_owned(), json!("SKU-42")), ("1".to_owned(), json!("42")), ("id".to_owned(), json!("42"))]);
+        let render = |template: Value| render_with_captures(&template, &input, &captures).unwrap();
+
+        assert_eq!(render(json!("{{$id}}")), json!("42"));
+        assert_eq!(render(json!("{{$0}} for {{customer.name}}")), json!("SKU-42 for Ankit"));
+        assert_eq!(render(json!("{{$1 * 2}}")), json!(84.0));
+    }
+}
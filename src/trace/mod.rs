@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::condition::LogicalOperator;
+use crate::error::Result;
+
+/// One call of a function, or of a list constraint, and what it returned.
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionTrace {
+    pub function: String,
+    pub args: Vec<Value>,
+    /// `None` when the call failed.
+    pub result: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FunctionTrace {
+    pub fn new(function: impl std::fmt::Debug, args: Vec<Value>, result: &Result<bool>) -> Self {
+        FunctionTrace {
+            function: format!("{:?}", function),
+            args,
+            result: result.as_ref().ok().copied(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// How one condition was evaluated. Chains (`and`, `or`, `xor`, `not`) hold the conditions they evaluated,
+/// a list condition holds one `element` entry per list element.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConditionTrace {
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_path: Option<String>,
+    /// The resolved input value, the list of a list condition or the result of an aggregate condition.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<FunctionTrace>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<ConditionTrace>,
+    /// `None` when the condition failed.
+    pub result: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ConditionTrace {
+    pub fn new(kind: &'static str, input_path: Option<&str>) -> Self {
+        ConditionTrace {
+            kind,
+            input_path: input_path.map(str::to_owned),
+            value: None,
+            functions: Vec::new(),
+            conditions: Vec::new(),
+            result: None,
+            error: None,
+        }
+    }
+
+    pub fn finish(&mut self, result: &Result<bool>) {
+        self.result = result.as_ref().ok().copied();
+        self.error = result.as_ref().err().map(|e| e.to_string());
+    }
+}
+
+/// How one rule was evaluated and what it contributed to the output.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleTrace {
+    pub rule: String,
+    pub chain: LogicalOperator,
+    pub conditions: Vec<ConditionTrace>,
+    pub matched: bool,
+    /// The outputs of the rule when it matched, empty otherwise.
+    pub output: HashMap<String, Value>,
+}
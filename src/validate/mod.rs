@@ -0,0 +1,358 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::condition::{ConditionExpr, ListConstraint};
+use crate::core::{Rule, RuleGroup};
+use crate::error::{arguments, type_name};
+use crate::function::list_function::{list_function_constraints_argument_number, ListFunction};
+use crate::function::numeric::{compare, Number};
+use crate::function::date;
+use crate::function::pattern::{invalid_pattern, takes_patterns};
+use crate::function::signature::{Arity, ValueType};
+use crate::function::{FunctionCall, FunctionRegistry, SingleInputFunction};
+use crate::loader::{LoadError, RuleFile};
+use crate::path;
+
+/// Checks the rules of a file before they are evaluated: rule names that are unique across all groups, since errors
+/// and traces only name the rule, valid paths, functions that are registered, the number and types of the arguments of
+/// functions and list constraints, and regular expressions, which are compiled into the registry on the way.
+/// Every problem is reported with the rule and the path of the offending field, like a rule file that does not parse.
+pub fn validate_file(file: &RuleFile, functions: &FunctionRegistry) -> Vec<LoadError> {
+    validate_added(&HashMap::new(), file.groups.iter().map(|(name, group)| (name.as_str(), group.rules.as_slice())), functions)
+}
+
+/// Like `validate_file`, for a group on its own; paths start at its `rules`.
+pub fn validate_group(group: &RuleGroup, functions: &FunctionRegistry) -> Vec<LoadError> {
+    let mut validator = Validator::new(functions);
+    validator.rules("", "", 0, &group.rules);
+    validator.diagnostics
+}
+
+/// Like `validate_file`, for a rule on its own; paths start at its fields.
+pub fn validate_rule(rule: &Rule, functions: &FunctionRegistry) -> Vec<LoadError> {
+    let mut validator = Validator::new(functions);
+    validator.rule("", rule);
+    validator.diagnostics
+}
+
+/// Like `validate_file`, for rules appended to `groups`: their names must not be used by the rules of `groups` either,
+/// and their paths are the ones they will have in `groups`.
+pub(crate) fn validate_added<'r>(
+    groups: &HashMap<String, RuleGroup>,
+    added: impl IntoIterator<Item = (&'r str, &'r [Rule])>,
+    functions: &FunctionRegistry,
+) -> Vec<LoadError> {
+    let mut validator = Validator::new(functions);
+    for (name, group) in groups {
+        for rule in &group.rules {
+            validator.names.insert(rule.name.clone(), name.clone());
+        }
+    }
+    for (name, rules) in added {
+        let offset = groups.get(name).map_or(0, |group| group.rules.len());
+        validator.rules(name, &format!("groups.{}.", name), offset, rules);
+    }
+    validator.diagnostics
+}
+
+struct Validator<'a> {
+    functions: &'a FunctionRegistry,
+    // The group every rule name seen so far is used in.
+    names: HashMap<String, String>,
+    diagnostics: Vec<LoadError>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(functions: &'a FunctionRegistry) -> Self {
+        Validator { functions, names: HashMap::new(), diagnostics: Vec::new() }
+    }
+
+    fn report(&mut self, rule: &Rule, path: String, message: String) {
+        self.diagnostics.push(LoadError { file: None, rule: Some(rule.name.clone()), path: Some(path), message });
+    }
+
+    // The rules of `group` from index `offset` on. `prefix` is the path of the group followed by a dot, or empty.
+    fn rules(&mut self, group: &str, prefix: &str, offset: usize, rules: &[Rule]) {
+        for (i, rule) in rules.iter().enumerate() {
+            let rule_path = format!("{}rules[{}]", prefix, offset + i);
+            match self.names.get(&rule.name) {
+                Some(other) if other == group => {
+                    self.report(rule, format!("{}.name", rule_path), format!("rule name `{}` is used more than once in the group", rule.name));
+                }
+                Some(other) => {
+                    let message = format!("rule name `{}` is also used in group `{}`", rule.name, other);
+                    self.report(rule, format!("{}.name", rule_path), message);
+                }
+                None => {
+                    self.names.insert(rule.name.clone(), group.to_owned());
+                }
+            }
+            self.rule(&format!("{}.", rule_path), rule);
+        }
+    }
+
+    fn rule(&mut self, prefix: &str, rule: &Rule) {
+        for (i, condition) in rule.conditions.iter().enumerate() {
+            self.condition(rule, &format!("{}conditions[{}]", prefix, i), condition);
+        }
+    }
+
+    fn condition(&mut self, rule: &Rule, at: &str, condition: &ConditionExpr) {
+        match condition {
+            ConditionExpr::Single(single) => {
+                self.input_path(rule, &format!("{}.single.input_path", at), &single.input_path);
+                self.functions(rule, &format!("{}.single", at), &single.functions, None);
+            }
+            ConditionExpr::List(list) => {
+                self.input_path(rule, &format!("{}.list.input_path", at), &list.input_path);
+                self.functions(rule, &format!("{}.list", at), &list.functions, None);
+                self.constraint(rule, &format!("{}.list.constraint", at), &list.constraint);
+            }
+            ConditionExpr::Aggregate(aggregate) => {
+                self.input_path(rule, &format!("{}.aggregate.input_path", at), &aggregate.input_path);
+                if !self.functions.aggregate.contains_key(&aggregate.aggregator) {
+                    self.report(rule, format!("{}.aggregate.aggregator", at), format!("aggregator `{:?}` is not registered", aggregate.aggregator));
+                }
+                // Every aggregator gives a number.
+                self.functions(rule, &format!("{}.aggregate", at), &aggregate.functions, Some(ValueType::Number));
+            }
+            ConditionExpr::And(conditions) | ConditionExpr::Or(conditions) | ConditionExpr::Xor(conditions) => {
+                let kind = match condition {
+                    ConditionExpr::And(_) => "and",
+                    ConditionExpr::Or(_) => "or",
+                    _ => "xor",
+                };
+                for (i, condition) in conditions.iter().enumerate() {
+                    self.condition(rule, &format!("{}.{}[{}]", at, kind, i), condition);
+                }
+            }
+            ConditionExpr::Not(condition) => self.condition(rule, &format!("{}.not", at), condition),
+        }
+    }
+
+    fn input_path(&mut self, rule: &Rule, at: &str, input_path: &str) {
+        if let Err(e) = path::parse(input_path) {
+            self.report(rule, at.to_owned(), e.to_string());
+        }
+    }
+
+    // `input` is the type of the value the functions are applied to, when it is known.
+    fn functions(&mut self, rule: &Rule, at: &str, calls: &[FunctionCall], input: Option<ValueType>) {
+        for (i, call) in calls.iter().enumerate() {
+            let at = format!("{}.functions[{}]", at, i);
+            if !self.functions.single.contains_key(&call.function) {
+                self.report(rule, format!("{}.function", at), format!("function `{:?}` is not registered", call.function));
+                continue;
+            }
+
+            // Functions registered without a signature are taken as they are.
+            let Some(signature) = self.functions.signatures.single.get(&call.function) else {
+                continue;
+            };
+            if let Some(input) = input {
+                if signature.input != ValueType::Any && signature.input != input {
+                    self.report(rule, format!("{}.function", at), format!("`{:?}` takes a {} input but is applied to a {}",
+                        call.function, signature.input, input));
+                }
+            }
+            if !signature.args.accepts(call.args.len()) {
+                let expected = match signature.args {
+                    Arity::Exactly(n) => arguments(n),
+                    Arity::AtLeast(n) => format!("at least {}", arguments(n)),
+                };
+                self.report(rule, format!("{}.args", at), format!("`{:?}` takes {} but got {}", call.function, expected, call.args.len()));
+            }
+            for (j, arg) in call.args.iter().enumerate() {
+                if !signature.arg_type.accepts(arg) {
+                    self.report(rule, format!("{}.args[{}]", at, j), format!("`{:?}` takes {} arguments but got {} [{}]",
+                        call.function, signature.arg_type, argument_label(arg), arg));
+                }
+            }
+            if takes_patterns(call.function) {
+                for (j, pattern) in call.args.iter().enumerate() {
+                    let Some(pattern) = pattern.as_str() else { continue };
+                    if let Err(e) = self.functions.patterns.get(pattern) {
+                        self.report(rule, format!("{}.args[{}]", at, j), invalid_pattern(pattern, &e));
+                    }
+                }
+            }
+            for (j, arg) in call.args.iter().enumerate() {
+                if let Some(message) = arg.as_str().and_then(|arg| date::invalid_argument(call.function, &self.functions.dates, arg)) {
+                    self.report(rule, format!("{}.args[{}]", at, j), message);
+                }
+            }
+            if let Some(message) = date::invalid_arguments(call.function, &call.args) {
+                self.report(rule, format!("{}.args", at), message);
+            }
+            if let Some((min, max)) = range_bounds(call) {
+                if compare(min, max, 0.0) == Ordering::Greater {
+                    self.report(rule, format!("{}.args", at), format!("`{:?}` has a lower bound [{}] above its upper bound [{}]",
+                        call.function, call.args[0], call.args[1]));
+                }
+            }
+        }
+    }
+
+    fn constraint(&mut self, rule: &Rule, at: &str, constraint: &ListConstraint) {
+        if !self.functions.list.contains_key(&constraint.function) {
+            self.report(rule, format!("{}.function", at), format!("list function `{:?}` is not registered", constraint.function));
+        }
+        let expected = list_function_constraints_argument_number(constraint.function) as usize;
+        if constraint.args.len() != expected {
+            self.report(rule, format!("{}.args", at), format!("`{:?}` takes {} but got {}", constraint.function, arguments(expected), constraint.args.len()));
+            return;
+        }
+        for (i, arg) in constraint.args.iter().enumerate() {
+            if *arg < 0 {
+                self.report(rule, format!("{}.args[{}]", at, i), format!("`{:?}` takes no negative arguments but got {}", constraint.function, arg));
+            }
+        }
+        if matches!(constraint.function, ListFunction::AtLeastFraction | ListFunction::AtMostFraction) && constraint.args[1] == 0 {
+            self.report(rule, format!("{}.args[1]", at), format!("the denominator of `{:?}` is 0", constraint.function));
+        }
+    }
+}
+
+// The bounds of a range function, when both are numbers.
+fn range_bounds(call: &FunctionCall) -> Option<(Number, Number)> {
+    match call.function {
+        SingleInputFunction::Between | SingleInputFunction::BetweenEq | SingleInputFunction::NotBetween | SingleInputFunction::NotBetweenEq => {
+            match call.args.as_slice() {
+                [min, max] => Some((Number::from_value(min)?, Number::from_value(max)?)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn argument_label(arg: &Value) -> &'static str {
+    match arg {
+        Value::String(_) => "a string that is not a number",
+        _ => type_name(arg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::date::DateOptions;
+    use crate::function::FunctionOptions;
+    use crate::loader::{load_str, Format};
+    use serde_json::json;
+
+    fn diagnostics(rules: Value) -> Vec<String> {
+        let file: RuleFile = serde_json::from_value(json!({"groups": {"pricing": {"rules": rules}}})).unwrap();
+        validate_file(&file, &FunctionRegistry::default()).iter().map(|e| e.to_string()).collect()
+    }
+
+    fn single(functions: Value) -> Value {
+        json!({"single": {"input_path": "total", "functions": functions}})
+    }
+
+    #[test]
+    fn valid_rules() {
+        assert!(diagnostics(json!([
+            {"name": "a", "conditions": [
+                single(json!([{"function": "Between", "args": [1, "10"]}, {"function": "EqualAny", "args": ["x", 1]}])),
+                {"list": {"input_path": "items.price", "functions": [], "constraint": {"function": "AtLeastFraction", "args": [1, 3]}}},
+                {"not": {"aggregate": {"input_path": "items", "aggregator": "COUNT", "functions": [{"function": "Greater", "args": [2]}]}}},
+            ]},
+            {"name": "b", "conditions": []},
+        ])).is_empty());
+    }
+
+    #[test]
+    fn function_arguments() {
+        assert_eq!(diagnostics(json!([{"name": "a", "conditions": [
+            single(json!([
+                {"function": "Between", "args": [1]},
+                {"function": "Greater", "args": ["ten"]},
+                {"function": "StartsWith", "args": [5]},
+                {"function": "EqualAny"},
+                {"function": "Empty", "args": ["x"]},
+                {"function": "BetweenEq", "args": [null, true]},
+                {"function": "NotBetween", "args": [10, "9.5"]},
+                {"function": "MatchesAny", "args": ["^ok$", "[a-"]},
+                {"function": "OlderThan", "args": ["18 yaers"]},
+                {"function": "BetweenDates", "args": [null, 20240101]},
+                {"function": "After", "args": ["01.02.2024"]},
+                {"function": "BusinessHours", "args": ["17:00", "9:00"]},
+            ])),
+        ]}])), vec![
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[0].args: `Between` takes 2 arguments but got 1",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[1].args[0]: `Greater` takes number arguments but got a string that is not a number [\"ten\"]",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[2].args[0]: `StartsWith` takes string arguments but got number [5]",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[3].args: `EqualAny` takes at least 1 argument but got 0",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[4].args: `Empty` takes 0 arguments but got 1",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[5].args[1]: `BetweenEq` takes number or null arguments but got boolean [true]",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[6].args: `NotBetween` has a lower bound [10] above its upper bound [\"9.5\"]",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[7].args[1]: invalid pattern [[a-]: unclosed character class",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[8].args[0]: `OlderThan` takes a duration but got [\"18 yaers\"]",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[9].args[1]: `BetweenDates` takes string or null arguments but got number [20240101]",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[10].args[0]: `After` takes a date but got [\"01.02.2024\"]",
+            "rule `a`: groups.pricing.rules[0].conditions[0].single.functions[11].args: `BusinessHours` has an opening time \"17:00\" not before its closing time \"9:00\"",
+        ]);
+    }
+
+    #[test]
+    fn date_arguments_are_read_with_the_date_formats() {
+        let rules = json!({"groups": {"pricing": {"rules": [{"name": "a", "conditions": [
+            single(json!([{"function": "BetweenDates", "args": ["01.02.2024", null]}])),
+        ]}]}}});
+        let file: RuleFile = serde_json::from_value(rules).unwrap();
+        assert_eq!(validate_file(&file, &FunctionRegistry::default()).len(), 1);
+
+        let dates = DateOptions { formats: vec!["%d.%m.%Y".to_owned()], ..DateOptions::default() };
+        let functions = FunctionRegistry::with_options(FunctionOptions { dates, ..FunctionOptions::default() });
+        assert!(validate_file(&file, &functions).is_empty());
+    }
+
+    #[test]
+    fn list_and_aggregate_conditions() {
+        let list = |function: &str, args: Value| json!({"list": {"input_path": "items", "functions": [], "constraint": {"function": function, "args": args}}});
+        assert_eq!(diagnostics(json!([{"name": "a", "conditions": [
+            list("AtLeastFraction", json!([1])),
+            list("AtMostFraction", json!([1, 0])),
+            {"or": [list("AtLeast", json!([-1]))]},
+            {"aggregate": {"input_path": "items[", "aggregator": "SUM", "functions": [{"function": "Contains", "args": ["x"]}]}},
+        ]}])), vec![
+            "rule `a`: groups.pricing.rules[0].conditions[0].list.constraint.args: `AtLeastFraction` takes 2 arguments but got 1",
+            "rule `a`: groups.pricing.rules[0].conditions[1].list.constraint.args[1]: the denominator of `AtMostFraction` is 0",
+            "rule `a`: groups.pricing.rules[0].conditions[2].or[0].list.constraint.args[0]: `AtLeast` takes no negative arguments but got -1",
+            "rule `a`: groups.pricing.rules[0].conditions[3].aggregate.input_path: invalid path [items[]: missing `]`",
+            "rule `a`: groups.pricing.rules[0].conditions[3].aggregate.functions[0].function: `Contains` takes a string input but is applied to a number",
+        ]);
+    }
+
+    #[test]
+    fn unique_rule_names_and_registered_functions() {
+        let rule = json!({"name": "a", "conditions": [single(json!([{"function": "Equal", "args": [1]}]))]});
+        assert_eq!(diagnostics(json!([rule, rule])), vec![
+            "rule `a`: groups.pricing.rules[1].name: rule name `a` is used more than once in the group",
+        ]);
+
+        let file: RuleFile = serde_json::from_value(json!({"groups": {"fraud": {"rules": [rule]}, "pricing": {"rules": [rule]}}})).unwrap();
+        let diagnostics: Vec<String> = validate_file(&file, &FunctionRegistry::default()).iter().map(|e| e.to_string()).collect();
+        assert_eq!(diagnostics, vec!["rule `a`: groups.pricing.rules[0].name: rule name `a` is also used in group `fraud`"]);
+
+        let mut functions = FunctionRegistry::default();
+        functions.single.remove(&crate::function::SingleInputFunction::Equal);
+        let rule: Rule = serde_json::from_value(rule).unwrap();
+        assert_eq!(validate_rule(&rule, &functions)[0].to_string(),
+            "rule `a`: conditions[0].single.functions[0].function: function `Equal` is not registered");
+    }
+
+    #[test]
+    fn loading_validates_rules() {
+        let source = r#"{"groups": {"pricing": {"rules": [
+            {"name": "bulk", "conditions": [{"single": {"input_path": "quantity", "functions": [{"function": "Greater", "args": []}]}}]}
+        ]}}}"#;
+        let err = load_str(source, Format::Json, &FunctionRegistry::default()).unwrap_err();
+
+        assert_eq!(err.code(), "invalid_rules");
+        assert_eq!(err.to_string(), "rule `bulk`: groups.pricing.rules[0].conditions[0].single.functions[0].args: `Greater` takes 1 argument but got 0");
+    }
+}